@@ -1,17 +1,20 @@
 
 use crate::properties::*;
 use crate::window::*;
-use crate::event::{Event, EventListeners};
-use crate::command::{CommandTrait as _, CommandSharedState};
+use crate::event::{Event, EventListeners, HoverState, with_local, drag_events};
+use crate::command::{CommandTrait as _, CommandSharedState, WidgetOutputs, PointerPosition, PointerState};
 use crate::config::{BarConfig, BarConfigWidget};
 use crate::draw::{Drawable, DrawableSet, DrawFGInfo};
 use crate::font::Font;
 use crate::utils::Log;
 use crate::command::state::StateSingleton;
+use crate::command::var::VarSingleton;
+use crate::command::wakeup::WakeupSingleton;
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::os::unix::io::RawFd;
 
 struct Widget {
     properties: WidgetProperties,
@@ -28,6 +31,7 @@ struct Widget {
     cmd_out: String,
     drawinfo: DrawFGInfo,
     mouse_over: bool,
+    hover: HoverState,
     needs_redraw: bool
 }
 
@@ -39,6 +43,7 @@ pub struct Bar {
     default_bg: Drawable,
 
     current: BarPropertiesCurrent,
+    hover: HoverState,
 
     offset: i16,
     middle_left: i16,
@@ -46,9 +51,17 @@ pub struct Bar {
     fonts: HashMap<String, Font>,
     geometry: WindowGeometry,
     fake_geometry: WindowGeometry,
+    // Area covered by the trigger window, if it's mapped
+    trigger: Option<(i16, i16, u16, u16)>,
+    // Pointer position during the last refresh, to detect dragging
+    last_pointer: Option<(i16, i16)>,
     window: Window,
     cmdstate: RefCell<CommandSharedState>,
-    event_listeners: RefCell<EventListeners>
+    // Outputs shared between widgets, by command and interval
+    command_outputs: RefCell<HashMap<(u64, u32), (Instant, String)>>,
+    event_listeners: RefCell<EventListeners>,
+    // Something changed during the last refresh, so another one is needed to settle
+    active: bool
 }
 
 fn create_widgets(widgets: &Vec<BarConfigWidget>, listeners: &mut EventListeners, cmd: &mut CommandSharedState) -> Vec<RefCell<Widget>> {
@@ -66,6 +79,7 @@ fn create_widgets(widgets: &Vec<BarConfigWidget>, listeners: &mut EventListeners
                 drawinfo: DrawFGInfo::default(),
                 current,
                 mouse_over: false,
+                hover: HoverState::default(),
                 needs_redraw: false
         })}).collect()
 }
@@ -77,6 +91,7 @@ impl Bar {
 
         let mut cmdstate = CommandSharedState::new();
         cmdstate.get::<StateSingleton>(0).initialize(&cfg.states);
+        cmdstate.get::<VarSingleton>(0).initialize(&cfg.vars);
 
         let event_listeners = RefCell::new(EventListeners::new());
         let properties = BarProperties::from(&cfg.properties, &mut event_listeners.borrow_mut(), &mut cmdstate);
@@ -93,15 +108,18 @@ impl Bar {
         }).collect();
 
         let mut bar = Self {properties, widgets_left, widgets_right, window, 
-            geometry: WindowGeometry::new(), fake_geometry: WindowGeometry::new(),
+            geometry: WindowGeometry::new(), fake_geometry: WindowGeometry::new(), trigger: None, last_pointer: None,
             current,
+            hover: HoverState::default(),
             cmdstate: RefCell::new(cmdstate),
+            command_outputs: RefCell::new(HashMap::new()),
             default_bg: Drawable::from(cfg.default_bg),
             fonts,
             offset: 0,
             middle_left: 10000,
             middle_right: 0,
-            event_listeners
+            event_listeners,
+            active: false
         };
         bar.refresh(true);
         bar
@@ -112,13 +130,12 @@ impl Bar {
         events: &Vec<Event>, 
         force: bool, 
         bar_redraw: bool, 
-        mx: i16, my: i16) -> i16 
+        pointer: Option<(i16, i16)>) -> i16 
     {
         let mut widget_cursor = 0i16;
         
         let bar = &self.current;
         let height = bar.height;
-        let e = events;
 
         let widgets = match side {
             true => self.widgets_left.iter(),
@@ -126,14 +143,32 @@ impl Bar {
         };
 
         let mut width_change = 0;
+        let mut active = false;
+
+        let (scrw, scrh) = (self.window.screen_width(), self.window.screen_height());
+        let (bar_x, bar_y, _, _) = self.geometry.on_screen(scrw, scrh);
+        let (scroll_x, scroll_y) = self.window.get_scroll();
+        let moved = pointer.is_some() && pointer.map(|x| x.0) != self.last_pointer.map(|x| x.0);
 
         for i in widgets {
             let mut i = i.borrow_mut();
 
             // Determine if mouse is inside widget
-            let m = self.fake_geometry
-                .has_point_cropped(mx, my, self.window.screen_width(), self.window.screen_height(),
-                                   i.last_x, 0, i.width_max, height);
+            let m = pointer.is_some_and(|(mx, my)| self.fake_geometry
+                .has_point_cropped(mx, my, scrw, scrh, i.last_x, 0, i.width_max, height));
+
+            let mut local = vec!();
+            let (hover_delay, leave_delay) = (i.current.hover_delay, i.current.leave_delay);
+            i.hover.update(m, hover_delay, leave_delay, &mut local);
+
+            // Dragging is reported to the widget the button was pressed over, even when the pointer leaves it
+            let press = self.window.get_press().filter(|(_, px, py)| self.geometry
+                .has_point_cropped(*px, *py, scrw, scrh, i.last_x, 0, i.width_max, height));
+            if let (Some((button, _, _)), true) = (press, moved) {
+                drag_events(button, &mut local);
+            }
+            let merged = with_local(events, local);
+            let e = merged.as_ref().unwrap_or(events);
 
             // Get widget properties and determine whether they changed
             let new_current = i.properties.as_current(e,m);
@@ -145,23 +180,67 @@ impl Bar {
 
             // Update widget text
             let cmdstate = &mut self.get_cmd_state();
+            let mut outputs = self.command_outputs.borrow_mut();
 
-            if force || i.last_time_updated.elapsed().as_millis() > (i.current.interval * 1000.0) as u128
-                     || i.last_event_updated != i.properties.command.get_event(e,m) 
-                     || i.current.command.updated(cmdstate) {
-                     
-                let new_cmd_out = i.current.command.execute(cmdstate);
-                i.last_time_updated = Instant::now();
+            let event_changed = i.last_event_updated != i.properties.command.get_event(e,m);
+            let key = (i.current.command.id(), i.current.interval.to_bits());
+
+            let new_output: Option<(Instant, String)> = if !i.current.source.is_empty() {
+                // Widgets with a source display another widget's output
+                cmdstate.get::<WidgetOutputs>(0).outputs.get(&i.current.source)
+                    .filter(|(time, _)| force || *time > i.last_time_updated)
+                    .cloned()
+            }
+            else {
+                // Output of the same command run by another widget within the interval
+                let shared = outputs.get(&key)
+                    .filter(|(time, _)| *time > i.last_time_updated && time.elapsed().as_secs_f32() < i.current.interval)
+                    .cloned();
+
+                if force || i.last_time_updated.elapsed().as_millis() > (i.current.interval * 1000.0) as u128
+                         || event_changed
+                         || shared.is_some()
+                         || i.current.command.updated(cmdstate) {
+
+                    match shared {
+                        Some(x) if !event_changed => Some(x),
+                        _ => {
+                            let output = (Instant::now(), i.current.command.execute(cmdstate));
+                            outputs.insert(key, output.clone());
+                            Some(output)
+                        }
+                    }
+                }
+                else {None}
+            };
+
+            let new_output_received = new_output.is_some();
+            if let Some((time, new_cmd_out)) = new_output {
+                i.last_time_updated = time;
                 i.last_event_updated = i.properties.command.get_event(e,m);
 
                 if new_cmd_out != i.cmd_out {
                     i.needs_redraw = true;
                     i.cmd_out = new_cmd_out;
                 }
+                if !i.current.id.is_empty() {
+                    cmdstate.get::<WidgetOutputs>(0).outputs.insert(i.current.id.clone(), (time, i.cmd_out.clone()));
+                }
             }
 
-            // Perform action
+            // Perform action, knowing where the pointer is relative to the widget
+            cmdstate.get::<PointerState>(0).current = pointer.map(|(mx, my)| PointerPosition {
+                x: ((mx - bar_x - i.last_x) as f64 / i.width_max.max(1) as f64).clamp(0.0, 1.0),
+                y: ((my - bar_y) as f64 / height.max(1) as f64).clamp(0.0, 1.0),
+                scroll_x, scroll_y
+            });
             i.current.action.execute(cmdstate);
+            cmdstate.get::<PointerState>(0).current = None;
+
+            // Actions are performed on every frame while their event lasts
+            if new_output_received || i.needs_redraw || i.properties.action.get_event(e,m) != Event::default() {
+                active = true;
+            }
             
             if i.needs_redraw {
                 // New draw info
@@ -189,6 +268,7 @@ impl Bar {
             widget_cursor += i.width_max as i16;
         }
 
+        self.active |= active;
         widget_cursor 
     }
 
@@ -211,24 +291,29 @@ impl Bar {
 
     pub fn refresh(&mut self, force: bool) {
 
-        let (mx, my) = self.window.get_pointer();
         let e = &self.event_listeners.borrow_mut().get(self);
+        let pointer = self.window.get_pointer();
         
         // Determine if mouse is inside bar
-        let bm = self.fake_geometry.has_point(mx, my, self.window.screen_width(), self.window.screen_height());
+        let bm = pointer.is_some_and(|(mx, my)| self.fake_geometry.has_point(mx, my, self.window.screen_width(), self.window.screen_height()));
+
+        let mut hover = vec!();
+        self.hover.update(bm, self.current.hover_delay, self.current.leave_delay, &mut hover);
         
         // Get bar properties and determine whether they changed
-        let new_current = self.properties.as_current(e,bm);
+        let new_current = self.properties.as_current(with_local(e, hover).as_ref().unwrap_or(e),bm);
 
         let bar_redraw = if new_current != self.current {
             self.current = new_current;
             true
         }
         else {false};
+        self.active = bar_redraw;
 
         // Refresh widgets & calculate width
-        let width_left  = self.refresh_widgets(true,  e, force, bar_redraw, mx, my);
-        let width_right = self.refresh_widgets(false, e, force, bar_redraw, mx, my);
+        let width_left  = self.refresh_widgets(true,  e, force, bar_redraw, pointer);
+        let width_right = self.refresh_widgets(false, e, force, bar_redraw, pointer);
+        self.last_pointer = pointer;
 
         let bar = &self.current;
         let height = bar.height;
@@ -262,6 +347,19 @@ impl Bar {
         }
         // Redraw on exposure
         else {e.iter().find(|x| x.is_expose()) != None};
+        self.active |= global_redraw;
+
+        // Pointer events are only received over windows, so the area of a bar moved or hidden on hover is covered by the trigger window
+        let (scrw, scrh) = (self.window.screen_width(), self.window.screen_height());
+        let p = &self.properties;
+        let hover_geometry = p.xoff.mouse_dependent() || p.yoff.mouse_dependent() || p.alignment.mouse_dependent() || p.visible.mouse_dependent();
+        let covered = self.geometry.visible && self.geometry.on_screen(scrw, scrh) == self.fake_geometry.on_screen(scrw, scrh);
+
+        let trigger = if hover_geometry && !covered {Some(self.fake_geometry.on_screen(scrw, scrh))} else {None};
+        if trigger != self.trigger {
+            self.trigger = trigger;
+            self.window.configure_trigger(trigger.map(|_| &self.fake_geometry)).log("bar refresh - trigger window reconfiguration");
+        }
 
         // Redraw widgets
         self.draw_widgets(&self.widgets_left,  global_redraw, 0);
@@ -293,6 +391,47 @@ impl Bar {
         self.window.flush();
     }
 
+    /// Descriptors to wait for and the time of the next needed refresh, `None` if it's only needed when a descriptor becomes readable
+    pub fn wakeup(&self) -> (Vec<RawFd>, Option<Instant>) {
+        let mut state = self.get_cmd_state();
+        let wakeup = state.get::<WakeupSingleton>(0);
+
+        let mut fds = vec!(self.window.fd());
+        fds.extend(self.event_listeners.borrow().fds());
+        fds.extend(wakeup.take_fds());
+
+        let mut deadline = wakeup.take_deadline();
+        let mut at = |t: Instant| deadline = Some(deadline.map_or(t, |x| x.min(t)));
+
+        if self.active {
+            at(Instant::now());
+        }
+        if let Some(t) = self.event_listeners.borrow().deadline() {
+            at(t);
+        }
+        if let Some(t) = self.hover.deadline() {
+            at(t);
+        }
+
+        // Widgets are updated once their interval has passed
+        for i in self.widgets_left.iter().chain(self.widgets_right.iter()) {
+            let i = i.borrow();
+            if let Some(t) = i.hover.deadline() {
+                at(t);
+            }
+            if !i.current.source.is_empty() {
+                continue;
+            }
+            let t = Duration::try_from_secs_f32(i.current.interval).ok()
+                .and_then(|x| i.last_time_updated.checked_add(x + Duration::from_millis(1)));
+            if let Some(t) = t {
+                at(t);
+            }
+        }
+
+        (fds, deadline)
+    }
+
     fn get_font(&self, font: &String) -> &Font {
         if let Some(f) = self.fonts.get(font) {
             f
@@ -3,15 +3,33 @@ use std::collections::HashMap;
 use std::any::{Any, TypeId};
 use std::hash::{Hasher, Hash};
 use std::collections::hash_map::DefaultHasher;
+use std::time::Instant;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use serde_yaml::{Value, from_value};
+use serde_yaml::{Value, Mapping, from_value};
 use serde::Deserialize;
 use dyn_clone::DynClone;
 
+use crate::utils::YAMLString;
+
 mod common;
 mod sysinfo;
 mod alsa;
+mod backlight;
+mod disk;
+mod netif;
+mod rate;
+mod expr;
+mod conditional;
+mod timer;
+mod file;
+mod mail;
+mod privacy;
+mod plugin;
+mod script;
 pub mod state;
+pub mod var;
+pub mod wakeup;
 
 // A general trait for commands, concrete implementations are in command/ directory
 pub trait CommandTrait: 'static + Any + DynClone {
@@ -27,6 +45,28 @@ pub struct CommandSharedState {
     parts: HashMap<(TypeId, u64), Box<dyn Any>>
 }
 
+/// Outputs of widgets that have an `id`, along with the time they were updated
+#[derive(Default)]
+pub struct WidgetOutputs {
+    pub outputs: HashMap<String, (Instant, String)>
+}
+
+/// Pointer position relative to the widget whose action is performed, from 0.0 to 1.0,
+/// along with smooth scrolling since the last refresh, in scroll steps
+#[derive(Clone, Copy)]
+pub struct PointerPosition {
+    pub x: f64,
+    pub y: f64,
+    pub scroll_x: f64,
+    pub scroll_y: f64
+}
+
+/// Set only while an action is performed with the pointer over the bar
+#[derive(Default)]
+pub struct PointerState {
+    pub current: Option<PointerPosition>
+}
+
 // A command container used in other program structs
 #[derive(Clone)]
 pub struct Command {
@@ -40,12 +80,45 @@ struct CommandObject {
     r#type: Option<String>,
     core: Option<usize>,
     network: Option<String>,
+    family: Option<String>,
     mountpoint: Option<String>,
+    label: Option<String>,
+    uuid: Option<String>,
+    select: Option<String>,
     card: Option<String>,
+    element: Option<String>,
+    capture: Option<bool>,
+    channel: Option<String>,
     volume: Option<String>,
+    device: Option<String>,
+    brightness: Option<String>,
     state_machine: Option<String>,
     state: Option<String>,
-    traverse: Option<i32>
+    traverse: Option<i32>,
+    of: Option<Value>,
+    smoothing: Option<f64>,
+    window: Option<f32>,
+    unit: Option<String>,
+    expr: Option<String>,
+    vars: Option<Mapping>,
+    precision: Option<usize>,
+    above: Option<f64>,
+    below: Option<f64>,
+    equals: Option<Value>,
+    matches: Option<String>,
+    then: Option<Value>,
+    r#else: Option<Value>,
+    cases: Option<Vec<Value>>,
+    name: Option<String>,
+    duration: Option<Value>,
+    path: Option<String>,
+    line: Option<Value>,
+    paths: Option<Vec<String>>,
+    value: Option<Value>,
+    by: Option<f64>,
+    min: Option<f64>,
+    max: Option<f64>,
+    code: Option<String>
 }
 
 
@@ -57,6 +130,13 @@ impl PartialEq for Command {
     }
 }
 
+impl Command {
+    /// Hash of the command's description, equal for identical commands
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
 impl CommandTrait for Command {
     fn execute(&self, state: &mut CommandSharedState) -> String {self.cmd.execute(state)}
     fn updated(&self, state: &mut CommandSharedState) -> bool {self.cmd.updated(state)}
@@ -78,12 +158,23 @@ impl From<Value> for Command {
     fn from(val: Value) -> Self {
         // Coming up with a better implementation is left as an exercise for the reader
         
-        let mut hasher = DefaultHasher::new();
-        format!("{:?}", val).hash(&mut hasher);
-        Self { cmd: new_command(val), id: hasher.finish() }
+        let id = hash_value(&val);
+        Self { cmd: new_command(val), id }
     }
 }
 
+fn hash_value(val: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", val).hash(&mut hasher);
+    hasher.finish()
+}
+
+// Unique for every parsed command, unlike the hash of its description which identical commands share
+fn instance_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
 fn new_command(val: Value) -> Box<dyn CommandTrait> {
     match val {
         Value::String(s) => {
@@ -102,6 +193,16 @@ fn new_command(val: Value) -> Box<dyn CommandTrait> {
                         .map(|s| Command::from(s.to_owned()))
                         .collect())),
         Value::Mapping(obj) => {
+            // Used by commands that keep their own state between calls, shared by identical commands
+            let id = hash_value(&Value::Mapping(obj.clone()));
+            // Used by commands that report changes since they were last run, as each widget runs them separately
+            let instance = instance_id();
+
+            // Plugins take any options, so they are handled before the object is parsed
+            if obj.get(&Value::String("type".to_string())) == Some(&Value::String("plugin".to_string())) {
+                return Box::new(plugin::PluginCommand::new(obj));
+            }
+
             let object: CommandObject = from_value(Value::Mapping(obj)).unwrap();
             
             if let Some(t) = object.r#type {
@@ -114,11 +215,29 @@ fn new_command(val: Value) -> Box<dyn CommandTrait> {
                         Some(&"freq")  => Box::new(sysinfo::CPUFreqCommand(object.core)),
                         _ => panic!("Unknown command type {}", t)
                     }
+                    Some(&"disk") if words.get(1) == Some(&"read") || words.get(1) == Some(&"write") => {
+                        let ty = match words.get(1) {
+                            Some(&"read") => disk::DiskIOType::Read,
+                            _ => disk::DiskIOType::Write
+                        };
+                        if words.get(2) != Some(&"bytes") {
+                            panic!("Unknown command type {}", t)
+                        }
+                        let time = match words.get(3) {
+                            Some(&"since") => sysinfo::NetInfoTime::Since,
+                            Some(&"total") => sysinfo::NetInfoTime::Total,
+                            None => sysinfo::NetInfoTime::PerSecond,
+                            _ => panic!("Unknown command type {}", t)
+                        };
+                        Box::new(disk::DiskIOCommand {ty, time, device: object.device, mountpoint: object.mountpoint})
+                    }
                     Some(&"mem") | Some(&"swap") | Some(&"disk") => {
                         let ty = match words.get(0) {
                             Some(&"mem") => sysinfo::MemoryInfoType::RAM,
                             Some(&"swap") => sysinfo::MemoryInfoType::Swap,
-                            Some(&"disk") => sysinfo::MemoryInfoType::Disk(object.mountpoint),
+                            Some(&"disk") => sysinfo::MemoryInfoType::Disk(sysinfo::DiskSelector::new(
+                                    object.mountpoint, object.device, object.label, object.uuid, object.select)
+                                ),
                             _ => panic!("Unknown command type {}", t)
                         };
                         let val = match words.get(1) {
@@ -130,6 +249,19 @@ fn new_command(val: Value) -> Box<dyn CommandTrait> {
                         };
                         Box::new(sysinfo::MemoryInfoCommand {ty, val})
                     }
+                    Some(&"net") if words.get(1) == Some(&"address") || words.get(1) == Some(&"state") => {
+                        match words.get(1) {
+                            Some(&"address") => {
+                                let family = object.family.map(|f| match &f[..] {
+                                    "ipv4" => netif::AddressFamily::IPv4,
+                                    "ipv6" => netif::AddressFamily::IPv6,
+                                    _ => panic!("Unknown address family '{}' (expected 'ipv4' or 'ipv6')", f)
+                                });
+                                Box::new(netif::NetAddressCommand {name: object.network, family})
+                            }
+                            _ => Box::new(netif::NetStateCommand(object.network))
+                        }
+                    }
                     Some(&"net") => {
                         let ty = match words.get(1) {
                             Some(&"upload") => sysinfo::NetInfoType::Upload,
@@ -152,17 +284,181 @@ fn new_command(val: Value) -> Box<dyn CommandTrait> {
                         Box::new(sysinfo::NetInfoCommand {ty,val,time, name: object.network})
                     }
                     Some(&"alsa") => {
-                        if let Some(&"volume") = words.get(2) {
-                            match words.get(1) {
-                                Some(&"get") => Box::new(alsa::ALSAGetVolumeCommand(object.card)),
-                                Some(&"set") => Box::new(alsa::ALSASetVolumeCommand(
-                                        object.card,
-                                        alsa::VolumeChange::new(object.volume.unwrap_or_default()))
-                                    ),
-                                _ => {panic!("Unknown command type {}", t)}
+                        let elem = alsa::ALSAElement::new(object.card, object.element, object.capture, object.channel);
+
+                        match (words.get(1), words.get(2)) {
+                            (Some(&"volume"), Some(&"get")) | (Some(&"get"), Some(&"volume")) => Box::new(alsa::ALSAGetVolumeCommand(elem, instance)),
+                            (Some(&"volume"), Some(&"set")) | (Some(&"set"), Some(&"volume")) => Box::new(alsa::ALSASetVolumeCommand(
+                                    elem,
+                                    alsa::VolumeChange::new(object.volume.unwrap_or_default()))
+                                ),
+                            (Some(&"db"), Some(&"get")) => Box::new(alsa::ALSAGetDbCommand(elem, instance)),
+                            (Some(&"mute"), Some(&"get")) => Box::new(alsa::ALSAGetMuteCommand(elem, instance)),
+                            (Some(&"mute"), Some(&"toggle")) => Box::new(alsa::ALSAToggleMuteCommand(elem)),
+                            _ => {panic!("Unknown command type {}", t)}
+                        }
+                    }
+                    Some(&"wifi") => match words.get(1) {
+                        Some(&"ssid") => Box::new(netif::WifiSSIDCommand(object.network)),
+                        Some(&"quality") => Box::new(netif::WifiQualityCommand(object.network)),
+                        _ => panic!("Unknown command type {}", t)
+                    }
+                    Some(&"backlight") => match words.get(1) {
+                        Some(&"get") => Box::new(backlight::BacklightGetCommand(object.device)),
+                        Some(&"set") => Box::new(backlight::BacklightSetCommand(
+                                object.device,
+                                alsa::VolumeChange::new(object.brightness.unwrap_or_default()))
+                            ),
+                        _ => panic!("Unknown command type {}", t)
+                    }
+                    Some(&"rate") | Some(&"ema") | Some(&"min") | Some(&"max") => {
+                        let of = Command::from(object.of.expect(&format!("'{}' command requires 'of' to be set", t)));
+                        // An average without smoothing would just repeat its input
+                        let default_smoothing = if t == "ema" {0.3} else {1.0};
+                        let smoothing = object.smoothing.unwrap_or(default_smoothing);
+                        let unit = object.unit.unwrap_or_default();
+
+                        if smoothing <= 0.0 || smoothing > 1.0 {
+                            panic!("'smoothing' must be in range (0, 1]");
+                        }
+
+                        match words.get(0) {
+                            Some(&"rate") => Box::new(rate::RateCommand {id, of, smoothing, unit: unit + "/s"}),
+                            Some(&"ema") => Box::new(rate::AverageCommand {id, of, smoothing, unit}),
+                            _ => Box::new(rate::ExtremumCommand {
+                                id, of, unit,
+                                window: object.window.filter(|x| *x > 0.0).unwrap_or(60.0),
+                                max: words.get(0) == Some(&"max")
+                            })
+                        }
+                    }
+                    Some(&"expr") => {
+                        let (names, vars): (Vec<_>, Vec<_>) = object.vars.unwrap_or_default().into_iter()
+                            .map(|(k,v)| (k.string().clone(), Command::from(v)))
+                            .unzip();
+                        let e = object.expr.expect("'expr' command requires 'expr' to be set");
+                        let expr = expr::Expr::parse(&e, &names)
+                            .unwrap_or_else(|x| panic!("Failed to parse expression '{}': {}", e, x));
+
+                        Box::new(expr::ExprCommand {expr, vars, precision: object.precision, unit: object.unit.unwrap_or_default()})
+                    }
+                    Some(&"script") => {
+                        let vars = object.vars.unwrap_or_default().into_iter()
+                            .map(|(k,v)| (k.string().clone(), Command::from(v)))
+                            .collect();
+                        let code = object.code.expect("'script' command requires 'code' to be set");
+
+                        Box::new(script::ScriptCommand::new(&code, vars)
+                            .unwrap_or_else(|x| panic!("Failed to compile script '{}': {}", code, x)))
+                    }
+                    Some(&"if") | Some(&"switch") => {
+                        let source = match (object.of, object.state_machine) {
+                            (Some(of), _) => conditional::ConditionSource::Command(Command::from(of)),
+                            (None, Some(sm)) => conditional::ConditionSource::StateMachine(sm),
+                            _ => panic!("'{}' command requires either 'of' or 'state_machine' to be set", t)
+                        };
+                        let default = Command::from(object.r#else.unwrap_or_else(|| Value::String(String::new())));
+
+                        let cases = if words.get(0) == Some(&"if") {
+                            let conditions = conditional::Condition::list(object.above, object.below, object.equals, object.matches);
+                            vec!((conditions, Command::from(object.then.expect("'if' command requires 'then' to be set"))))
+                        }
+                        else {
+                            object.cases.expect("'switch' command requires 'cases' to be set").into_iter()
+                                .map(|c| {
+                                    let case: CommandObject = from_value(c).expect("'switch' case must be an object");
+                                    let conditions = conditional::Condition::list(case.above, case.below, case.equals, case.matches);
+                                    (conditions, Command::from(case.then.expect("Every 'switch' case requires 'then' to be set")))
+                                })
+                                .collect()
+                        };
+
+                        Box::new(conditional::ConditionalCommand {instance, source, cases, default})
+                    }
+                    Some(&"timer") | Some(&"stopwatch") => {
+                        // Timers and stopwatches with the same name are separate clocks
+                        let name = format!("{}.{}", words[0], object.name.unwrap_or_else(|| "default".to_string()));
+
+                        let control = match words.get(1) {
+                            Some(&"start") => Some(timer::ClockControl::Start),
+                            Some(&"pause") => Some(timer::ClockControl::Pause),
+                            Some(&"toggle") => Some(timer::ClockControl::Toggle),
+                            Some(&"reset") => Some(timer::ClockControl::Reset),
+                            None => None,
+                            _ => panic!("Unknown command type {}", t)
+                        };
+                        let parse = |x: &Value| match x {
+                            Value::Number(n) => timer::TimerDuration::parse(&n.to_string()).unwrap_or_else(|e| panic!("{}", e)),
+                            Value::String(s) => timer::TimerDuration::parse(s).unwrap_or_else(|e| panic!("{}", e)),
+                            _ => panic!("Timer durations must be either numbers of seconds or strings like '25m'")
+                        };
+
+                        match (words[0], control) {
+                            (_, Some(c)) => Box::new(timer::ClockControlCommand(name, c)),
+                            ("stopwatch", None) => Box::new(timer::StopwatchCommand {instance, name}),
+                            _ => {
+                                let duration = match object.duration.expect("'timer' command requires 'duration' to be set") {
+                                    Value::Mapping(m) => {
+                                        if object.state_machine.is_none() {
+                                            panic!("Timer durations per state require 'state_machine' to be set");
+                                        }
+                                        timer::TimerDuration::PerState(m.iter().map(|(k,v)| (k.string().clone(), parse(v))).collect())
+                                    }
+                                    x => timer::TimerDuration::Fixed(parse(&x))
+                                };
+                                Box::new(timer::TimerCommand {instance, name, duration, state_machine: object.state_machine, state: object.state})
+                            }
+                        }
+                    }
+                    Some(&"file") | Some(&"tail") => {
+                        let path = crate::config::resolve_path(&object.path.unwrap_or_else(|| panic!("'{}' command requires 'path' to be set", t)));
+
+                        match words[0] {
+                            "tail" => Box::new(file::TailCommand(path, instance)),
+                            _ => {
+                                let line = match object.line {
+                                    None => file::FileLine::Number(1),
+                                    Some(Value::String(x)) if x == "all" => file::FileLine::All,
+                                    Some(Value::Number(n)) if n.as_i64().filter(|x| *x != 0).is_some() => file::FileLine::Number(n.as_i64().unwrap()),
+                                    Some(x) => panic!("'line' must be either \"all\" or a non-zero line number, found {:?}", x)
+                                };
+                                Box::new(file::FileCommand {path, line})
                             }
                         }
-                        else {panic!("Unknown command type {}", t)}
+                    }
+                    Some(&"mail") if words.get(1) == Some(&"unread") => {
+                        let paths = match (object.path, object.paths) {
+                            (Some(p), None) => vec!(p),
+                            (None, Some(p)) => p,
+                            _ => panic!("'mail_unread' command requires either 'path' or 'paths' to be set")
+                        };
+                        Box::new(mail::MailUnreadCommand {instance, paths})
+                    }
+                    Some(&"privacy") => Box::new(privacy::PrivacyCommand(match object.device.as_deref() {
+                        None => privacy::PrivacyDevice::Any,
+                        Some("camera") => privacy::PrivacyDevice::Camera,
+                        Some("microphone") => privacy::PrivacyDevice::Microphone,
+                        Some(x) => panic!("Unknown privacy device '{}' (expected 'camera' or 'microphone')", x)
+                    })),
+                    Some(&"var") => {
+                        let name = object.name.unwrap_or_else(|| panic!("'{}' command requires 'name' to be set", t));
+                        let value = match (object.value, object.of) {
+                            (Some(Value::String(x)), None) => Some(var::VarValue::Text(x)),
+                            (Some(Value::Number(x)), None) => Some(var::VarValue::Text(x.to_string())),
+                            (Some(Value::Bool(x)), None) => Some(var::VarValue::Text(x.to_string())),
+                            (None, Some(of)) => Some(var::VarValue::Command(Command::from(of))),
+                            (None, None) => None,
+                            _ => panic!("'{}' command requires either 'value' (a string or a number) or 'of' to be set", t)
+                        };
+                        let value = || value.unwrap_or_else(|| panic!("'{}' command requires either 'value' or 'of' to be set", t));
+
+                        match words.get(1) {
+                            Some(&"get") => Box::new(var::VarGetCommand {instance, name}),
+                            Some(&"set") => Box::new(var::VarSetCommand(name, value())),
+                            Some(&"append") => Box::new(var::VarAppendCommand(name, value())),
+                            Some(&"inc") => Box::new(var::VarIncCommand {name, by: object.by.unwrap_or(1.0), min: object.min, max: object.max}),
+                            _ => panic!("Unknown command type {}", t)
+                        }
                     }
                     Some(&"state") => {
                         let sm = object.state_machine.expect("'state_*' commands require 'state_machine' to be set");
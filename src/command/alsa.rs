@@ -1,24 +1,57 @@
 
-use crate::command::{CommandTrait, CommandSharedState};
+use crate::command::{CommandTrait, CommandSharedState, PointerState};
+use crate::command::wakeup;
+use crate::utils::LogType;
 
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
 use alsa::mixer::{Mixer, Selem, SelemId, SelemChannelId};
 
 #[derive(Clone, PartialEq)]
 pub enum VolumeChange {
-    Percent(isize,f32)
+    Percent(isize,f32),
+    // Exactly the percentage of the widget's width the pointer is at
+    Pointer
 }
 
+// Describes which mixer control a command refers to
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ALSAElement {
+    pub card: Option<String>,
+    pub element: Option<String>,
+    pub capture: bool,
+    pub channel: Option<SelemChannelId>
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ALSAGetVolumeCommand(pub ALSAElement, pub u64);
 #[derive(Clone, PartialEq)]
-pub struct ALSAGetVolumeCommand(pub Option<String>);
+pub struct ALSASetVolumeCommand(pub ALSAElement, pub VolumeChange);
 #[derive(Clone, PartialEq)]
-pub struct ALSASetVolumeCommand(pub Option<String>, pub VolumeChange);
+pub struct ALSAGetDbCommand(pub ALSAElement, pub u64);
+#[derive(Clone, PartialEq)]
+pub struct ALSAGetMuteCommand(pub ALSAElement, pub u64);
+#[derive(Clone, PartialEq)]
+pub struct ALSAToggleMuteCommand(pub ALSAElement);
+
 
+// Volume and mute state, used to detect changes
+type ALSAReading = Option<(Option<i64>, Option<bool>)>;
+
+// A mixer along with a counter of events, bumped by a helper thread waiting on that mixer
+struct ALSAMixer {
+    mixer: Option<Mixer>,
+    generation: Arc<AtomicUsize>,
+    handled: usize
+}
 
 struct ALSASingleton {
-    mixers: HashMap<String, Option<Mixer>>,
-    old_volume: Option<i64>
+    mixers: HashMap<String, ALSAMixer>,
+    // By command instance as well, so that every widget reading an element notices its changes
+    old_values: HashMap<(u64, ALSAElement), (usize, ALSAReading)>
 }
 
 impl CommandTrait for ALSAGetVolumeCommand {
@@ -26,13 +59,44 @@ impl CommandTrait for ALSAGetVolumeCommand {
         state.get::<ALSASingleton>(0).get_volume_percent(&self.0)
     }
     fn updated(&self, state: &mut CommandSharedState) -> bool {
-        state.get::<ALSASingleton>(0).has_volume_changed(&self.0)
+        state.get::<ALSASingleton>(0).has_changed(self.1, &self.0)
     }
 }
 
 impl CommandTrait for ALSASetVolumeCommand {
     fn execute(&self, state: &mut CommandSharedState) -> String {
-        state.get::<ALSASingleton>(0).set_volume_percent(&self.0, &self.1);
+        if let Some(change) = self.1.resolve(state) {
+            state.get::<ALSASingleton>(0).set_volume_percent(&self.0, &change);
+        }
+        String::new()
+    }
+}
+
+impl CommandTrait for ALSAGetDbCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        state.get::<ALSASingleton>(0).get_volume_db(&self.0)
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        state.get::<ALSASingleton>(0).has_changed(self.1, &self.0)
+    }
+}
+
+impl CommandTrait for ALSAGetMuteCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        match state.get::<ALSASingleton>(0).get_mute(&self.0) {
+            Some(true) => String::from("muted"),
+            Some(false) => String::from("unmuted"),
+            None => String::from("ERR")
+        }
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        state.get::<ALSASingleton>(0).has_changed(self.1, &self.0)
+    }
+}
+
+impl CommandTrait for ALSAToggleMuteCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        state.get::<ALSASingleton>(0).toggle_mute(&self.0);
         String::new()
     }
 }
@@ -40,6 +104,9 @@ impl CommandTrait for ALSASetVolumeCommand {
 
 impl VolumeChange {
     pub fn new(s: String) -> Self {
+        if s == "pointer" {
+            return Self::Pointer;
+        }
         let (rel, dir) = match s.chars().nth(0) {
             Some('+') => (1, 1f32),
             Some('-') => (1,-1f32),
@@ -63,64 +130,232 @@ impl VolumeChange {
             }
         }
     }
+
+    /// The change to apply now, `None` if it depends on the pointer which isn't over the bar
+    pub fn resolve(&self, state: &mut CommandSharedState) -> Option<Self> {
+        match self {
+            Self::Pointer => state.get::<PointerState>(0).current.map(|p| Self::Percent(0, p.x as f32 * 100f32)),
+            _ => Some(self.clone())
+        }
+    }
+}
+
+impl ALSAElement {
+    pub fn new(card: Option<String>, element: Option<String>, capture: Option<bool>, channel: Option<String>) -> Self {
+        let channel = channel.map(|c| match &c[..] {
+            "mono" | "front_left" => SelemChannelId::FrontLeft,
+            "front_right" => SelemChannelId::FrontRight,
+            "rear_left" => SelemChannelId::RearLeft,
+            "rear_right" => SelemChannelId::RearRight,
+            "front_center" => SelemChannelId::FrontCenter,
+            "woofer" => SelemChannelId::Woofer,
+            "side_left" => SelemChannelId::SideLeft,
+            "side_right" => SelemChannelId::SideRight,
+            "rear_center" => SelemChannelId::RearCenter,
+            _ => panic!("Unknown ALSA channel '{}'", c)
+        });
+        Self {card, element, capture: capture.unwrap_or(false), channel}
+    }
+
+    // Channels the reading is taken from - either the selected one or every channel present
+    fn channels(&self, selem: &Selem) -> Vec<SelemChannelId> {
+        match self.channel {
+            Some(c) => vec!(c),
+            None => SelemChannelId::all().iter()
+                .filter(|&&c| c != SelemChannelId::Unknown && c != SelemChannelId::Last)
+                .filter(|&&c| if self.capture {selem.has_capture_channel(c)} else {selem.has_playback_channel(c)})
+                .cloned().collect()
+        }
+    }
+
+    fn volume_range(&self, selem: &Selem) -> (i64, i64) {
+        if self.capture {selem.get_capture_volume_range()} else {selem.get_playback_volume_range()}
+    }
+
+    // Volume of the selected channel, or an average of all channels
+    fn volume(&self, selem: &Selem) -> Option<i64> {
+        let v = self.channels(selem).into_iter()
+            .filter_map(|c| if self.capture {selem.get_capture_volume(c)} else {selem.get_playback_volume(c)}.ok())
+            .collect::<Vec<_>>();
+
+        if v.is_empty() {None} else {Some(v.iter().sum::<i64>() / v.len() as i64)}
+    }
+
+    fn db(&self, selem: &Selem) -> Option<f32> {
+        let v = self.channels(selem).into_iter()
+            .filter_map(|c| if self.capture {selem.get_capture_vol_db(c)} else {selem.get_playback_vol_db(c)}.ok())
+            .map(|x| x.to_db())
+            .collect::<Vec<_>>();
+
+        if v.is_empty() {None} else {Some(v.iter().sum::<f32>() / v.len() as f32)}
+    }
+
+    // Element is considered muted only if every read channel is switched off
+    fn muted(&self, selem: &Selem) -> Option<bool> {
+        let v = self.channels(selem).into_iter()
+            .filter_map(|c| if self.capture {selem.get_capture_switch(c)} else {selem.get_playback_switch(c)}.ok())
+            .collect::<Vec<_>>();
+
+        if v.is_empty() {None} else {Some(v.iter().all(|&x| x == 0))}
+    }
+
+    fn set_volume(&self, selem: &Selem, value: i64) -> alsa::Result<()> {
+        match (self.channel, self.capture) {
+            (Some(c), false) => selem.set_playback_volume(c, value),
+            (Some(c), true) => selem.set_capture_volume(c, value),
+            (None, false) => selem.set_playback_volume_all(value),
+            (None, true) => self.channels(selem).into_iter()
+                .try_for_each(|c| selem.set_capture_volume(c, value))
+        }
+    }
+
+    fn set_switch(&self, selem: &Selem, value: i32) -> alsa::Result<()> {
+        match (self.channel, self.capture) {
+            (Some(c), false) => selem.set_playback_switch(c, value),
+            (Some(c), true) => selem.set_capture_switch(c, value),
+            (None, false) => selem.set_playback_switch_all(value),
+            (None, true) => selem.set_capture_switch_all(value)
+        }
+    }
+}
+
+
+impl ALSAMixer {
+    fn new(name: &str) -> Self {
+        let mixer = Mixer::new(name, false).ok();
+        let generation = Arc::new(AtomicUsize::new(0));
+
+        if mixer.is_some() {
+            let name = name.to_string();
+            let gen = Arc::clone(&generation);
+
+            // Mixer handles can't be shared between threads, so the helper opens its own
+            thread::spawn(move || {
+                let mixer = match Mixer::new(&name, false) {
+                    Ok(m) => m,
+                    Err(e) => {crate::log!(LogType::Error, "Failed to open mixer {} for event monitoring: {}", name, e); return}
+                };
+
+                loop {
+                    if let Err(e) = mixer.wait(None).and_then(|_| mixer.handle_events()) {
+                        crate::log!(LogType::Error, "Failed to wait for mixer {} events: {}", name, e);
+                        break;
+                    }
+                    gen.fetch_add(1, Ordering::Relaxed);
+                    wakeup::notify();
+                }
+            });
+        }
+
+        Self {mixer, generation, handled: 0}
+    }
+
+    fn generation(&self) -> usize {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    // Update cached element values if the helper thread reported any changes
+    fn get(&mut self) -> Option<&Mixer> {
+        let gen = self.generation();
+
+        if gen != self.handled {
+            self.handled = gen;
+            if let Some(Err(e)) = self.mixer.as_ref().map(|m| m.handle_events()) {
+                crate::log!(LogType::Error, "Failed to handle mixer events: {}", e);
+            }
+        }
+        self.mixer.as_ref()
+    }
 }
 
 
 impl ALSASingleton {
     fn new() -> Self {
-        Self {mixers: HashMap::new(), old_volume: None}
+        Self {mixers: HashMap::new(), old_values: HashMap::new()}
     }
 
-    fn with_selem<T,F>(&mut self, card: &Option<String>, f: F) -> Option<T> 
+    fn with_selem<T,F>(&mut self, elem: &ALSAElement, f: F) -> Option<T>
     where F: Fn(Selem) -> T
     {
         let default = String::from("default");
-        let name = card.as_ref().unwrap_or(&default);
+        let name = elem.card.as_ref().unwrap_or(&default);
+        let default_element = String::from(if elem.capture {"Capture"} else {"Master"});
+        let element = elem.element.as_ref().unwrap_or(&default_element);
 
-        let mixer = self.mixers.entry(name.to_string()).or_insert_with(|| {Mixer::new(&name[..], false).ok()});
-        mixer.as_ref().and_then(|m| m.find_selem(&SelemId::new("Master", 0)))
+        let mixer = self.mixers.entry(name.to_string()).or_insert_with(|| ALSAMixer::new(&name[..]));
+        mixer.get().and_then(|m| m.find_selem(&SelemId::new(element, 0)))
             .and_then(|s| Some(f(s)))
     }
 
-    fn get_volumes(&mut self, card: &Option<String>) -> Option<(i64,i64,i64)> {
+    fn get_volumes(&mut self, elem: &ALSAElement) -> Option<(i64,i64,i64)> {
 
-        self.with_selem(card, |selem| {
-            let (vmin, vmax) = selem.get_playback_volume_range();
-            selem.get_playback_volume(SelemChannelId::FrontLeft)
-                .ok().and_then(|v| Some((v,vmin,vmax)))
+        self.with_selem(elem, |selem| {
+            let (vmin, vmax) = elem.volume_range(&selem);
+            elem.volume(&selem).and_then(|v| Some((v,vmin,vmax)))
         }).flatten()
     }
 
-    fn get_volume_percent(&mut self, card: &Option<String>) -> String {
-        
-        self.get_volumes(card)
-        .and_then(|(v,vmin,vmax)| 
+    fn get_volume_percent(&mut self, elem: &ALSAElement) -> String {
+
+        self.get_volumes(elem)
+        .and_then(|(v,vmin,vmax)|
             Some(format!("{}%", ((v-vmin) as f32 / (vmax - vmin) as f32 * 100f32).round()))
         ).unwrap_or(String::from("ERR"))
     }
 
-    fn set_volume_percent(&mut self, card: &Option<String>, vol: &VolumeChange) {
+    fn get_volume_db(&mut self, elem: &ALSAElement) -> String {
+        self.with_selem(elem, |selem| elem.db(&selem)).flatten()
+            .map(|db| format!("{:.1}dB", db))
+            .unwrap_or(String::from("ERR"))
+    }
 
-        self.with_selem(card, |selem| {
+    fn get_mute(&mut self, elem: &ALSAElement) -> Option<bool> {
+        self.with_selem(elem, |selem| elem.muted(&selem)).flatten()
+    }
+
+    fn set_volume_percent(&mut self, elem: &ALSAElement, vol: &VolumeChange) {
+
+        self.with_selem(elem, |selem| {
 
-            #[allow(irrefutable_let_patterns)]
             if let VolumeChange::Percent(rel, change) = vol {
 
-                let (vmin, vmax) = selem.get_playback_volume_range();
-                let v = selem.get_playback_volume(SelemChannelId::FrontLeft).unwrap_or(vmin);
+                let (vmin, vmax) = elem.volume_range(&selem);
+                let v = elem.volume(&selem).unwrap_or(vmin);
 
                 let newvol = (v * *rel as i64 + (*change * (vmax-vmin) as f32/100f32) as i64).max(vmin).min(vmax);
-                selem.set_playback_volume_all(newvol)
+                elem.set_volume(&selem, newvol)
                     .err().and_then(|x| {eprintln!("Failed to set the new volume: {}", x); Some(())});
             }
         });
     }
 
-    fn has_volume_changed(&mut self, card: &Option<String>) -> bool {
-        let new_volume = self.get_volumes(card).and_then(|(v,_,_)| Some(v));
-        let updated = new_volume != self.old_volume;
+    fn toggle_mute(&mut self, elem: &ALSAElement) {
+
+        self.with_selem(elem, |selem| {
+            if let Some(muted) = elem.muted(&selem) {
+                elem.set_switch(&selem, muted as i32)
+                    .err().and_then(|x| {eprintln!("Failed to toggle mute: {}", x); Some(())});
+            }
+        });
+    }
+
+    fn has_changed(&mut self, instance: u64, elem: &ALSAElement) -> bool {
+        let gen = self.mixers.get(elem.card.as_ref().map(|x| &x[..]).unwrap_or("default"))
+            .map(|m| m.generation());
+
+        // Only read the element again if the mixer reported an event since the last check
+        let key = (instance, elem.clone());
+        match (self.old_values.get(&key), gen) {
+            (Some((old_gen, _)), Some(gen)) if *old_gen == gen => return false,
+            _ => ()
+        }
+
+        let new_value = self.with_selem(elem, |selem| (elem.volume(&selem), elem.muted(&selem)));
+        let (old_gen, old_value) = self.old_values.entry(key).or_insert((0, None));
+        let updated = new_value != *old_value;
 
-        if updated {self.old_volume = new_volume;}
+        *old_gen = gen.unwrap_or(0);
+        if updated {*old_value = new_value;}
         updated
     }
 }
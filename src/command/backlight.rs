@@ -0,0 +1,121 @@
+
+use crate::command::{CommandTrait, CommandSharedState};
+use crate::command::alsa::VolumeChange;
+use crate::command::wakeup::WakeupSingleton;
+use crate::utils::LogType;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::fs;
+use std::time::{Duration, Instant};
+
+const BACKLIGHT_DIR: &str = "/sys/class/backlight";
+// Sysfs doesn't report brightness changes made by the hardware, such as by Fn keys, so it's checked this often
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, PartialEq)]
+pub struct BacklightGetCommand(pub Option<String>);
+#[derive(Clone, PartialEq)]
+pub struct BacklightSetCommand(pub Option<String>, pub VolumeChange);
+
+
+#[derive(Default)]
+struct BacklightSingleton {
+    devices: HashMap<Option<String>, Option<PathBuf>>,
+    old_brightness: HashMap<Option<String>, Option<u64>>
+}
+
+impl CommandTrait for BacklightGetCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        state.get::<WakeupSingleton>(0).at(Instant::now() + POLL_INTERVAL);
+        state.get::<BacklightSingleton>(0).get_brightness_percent(&self.0)
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        state.get::<WakeupSingleton>(0).at(Instant::now() + POLL_INTERVAL);
+        state.get::<BacklightSingleton>(0).has_brightness_changed(&self.0)
+    }
+}
+
+impl CommandTrait for BacklightSetCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        if let Some(change) = self.1.resolve(state) {
+            state.get::<BacklightSingleton>(0).set_brightness_percent(&self.0, &change);
+        }
+        String::new()
+    }
+}
+
+
+fn read_value(path: PathBuf) -> Option<u64> {
+    fs::read_to_string(path).ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+}
+
+impl BacklightSingleton {
+
+    // Find the device directory, either the given one or the first one available
+    fn device(&mut self, device: &Option<String>) -> Option<PathBuf> {
+        self.devices.entry(device.clone()).or_insert_with(|| {
+            let path = match device {
+                Some(d) => Some(PathBuf::from(BACKLIGHT_DIR).join(d)),
+                None => fs::read_dir(BACKLIGHT_DIR).ok()
+                    .and_then(|dir| {
+                        let mut entries = dir.filter_map(|x| x.ok()).map(|x| x.path()).collect::<Vec<_>>();
+                        entries.sort();
+                        entries.into_iter().next()
+                    })
+            };
+
+            match path {
+                Some(p) if p.join("max_brightness").exists() => Some(p),
+                _ => {
+                    crate::log!(LogType::Error, "No backlight device found ({})", device.as_ref().map(|x| &x[..]).unwrap_or("any"));
+                    None
+                }
+            }
+        }).clone()
+    }
+
+    fn get_brightness(&mut self, device: &Option<String>) -> Option<(u64, u64)> {
+        self.device(device).and_then(|dev| {
+            let max = read_value(dev.join("max_brightness"))?;
+            let cur = read_value(dev.join("actual_brightness"))
+                .or_else(|| read_value(dev.join("brightness")))?;
+            Some((cur, max))
+        })
+    }
+
+    fn get_brightness_percent(&mut self, device: &Option<String>) -> String {
+        self.get_brightness(device)
+            .filter(|(_, max)| *max != 0)
+            .map(|(cur, max)| format!("{}%", (cur as f32 / max as f32 * 100f32).round()))
+            .unwrap_or_else(|| String::from("ERR"))
+    }
+
+    fn set_brightness_percent(&mut self, device: &Option<String>, change: &VolumeChange) {
+        let dev = match self.device(device) {
+            Some(d) => d,
+            None => return
+        };
+
+        if let Some((cur, max)) = self.get_brightness(device) {
+
+            if let VolumeChange::Percent(rel, change) = change {
+                let new = (cur as i64 * *rel as i64 + (*change * max as f32 / 100f32) as i64).max(0).min(max as i64);
+
+                if let Err(e) = fs::write(dev.join("brightness"), new.to_string()) {
+                    crate::log!(LogType::Error, "Failed to set the new brightness: {}", e);
+                }
+            }
+        }
+    }
+
+    fn has_brightness_changed(&mut self, device: &Option<String>) -> bool {
+        let new_brightness = self.get_brightness(device).map(|(v,_)| v);
+        let old_brightness = self.old_brightness.entry(device.clone()).or_insert(None);
+        let updated = new_brightness != *old_brightness;
+
+        if updated {*old_brightness = new_brightness;}
+        updated
+    }
+}
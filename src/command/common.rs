@@ -1,5 +1,6 @@
 
-use crate::command::{Command, CommandTrait, CommandSharedState};
+use crate::command::{Command, CommandTrait, CommandSharedState, PointerState};
+use crate::command::wakeup;
 use crate::config::config_dir;
 
 use std::collections::HashMap;
@@ -56,11 +57,18 @@ impl CommandTrait for MultiCommand {
 }
 
 impl CommandTrait for ShellCommand {
-    fn execute(&self, _state: &mut CommandSharedState) -> String {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
         let mut options = ScriptOptions::new();
         options.working_directory = Some(config_dir());
 
-        let (code, output, error) = run_script!(self.0, options)
+        // Actions get the pointer position through the environment
+        let script = match state.get::<PointerState>(0).current {
+            Some(p) => format!("export RAVENBAR_POINTER_X={} RAVENBAR_POINTER_Y={} RAVENBAR_SCROLL_X={} RAVENBAR_SCROLL_Y={}\n{}",
+                               p.x, p.y, p.scroll_x, p.scroll_y, self.0),
+            None => self.0.clone()
+        };
+
+        let (code, output, error) = run_script!(script, options)
             .expect("Failed to run shell script");
 
         if code != 0 {
@@ -126,6 +134,7 @@ impl PipeCommandProcess {
                     match i {
                         Ok(b'\n') => {
                             *current_line.lock().unwrap() = String::from_utf8_lossy(&buf[0..c]).into_owned();
+                            wakeup::notify();
                             c = 0; continue;
                         }
                         Ok(x) => {
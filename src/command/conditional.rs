@@ -0,0 +1,122 @@
+
+use crate::command::{Command, CommandTrait, CommandSharedState};
+use crate::command::state::StateSingleton;
+use crate::utils::find_human_readable;
+
+use regex::Regex;
+use serde_yaml::Value;
+
+#[derive(Clone, PartialEq)]
+pub struct ConditionalCommand {
+    pub instance: u64,
+    pub source: ConditionSource,
+    pub cases: Vec<(Vec<Condition>, Command)>,
+    pub default: Command
+}
+
+/// What the conditions are checked against
+#[derive(Clone, PartialEq)]
+pub enum ConditionSource {
+    Command(Command),
+    StateMachine(String)
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Condition {
+    Above(f64),
+    Below(f64),
+    EqualsNumber(f64),
+    EqualsText(String),
+    Matches(Pattern)
+}
+
+#[derive(Clone)]
+pub struct Pattern(pub Regex);
+
+// Index of the last chosen case by command instance, used to detect state machine changes
+#[derive(Default)]
+struct ConditionalState {
+    last_case: Option<usize>
+}
+
+
+impl CommandTrait for ConditionalCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let case = self.choose(state);
+        state.get::<ConditionalState>(self.instance).last_case = Some(case);
+
+        self.cases.get(case).map(|(_, cmd)| cmd).unwrap_or(&self.default).execute(state)
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        let mut updated = match &self.source {
+            ConditionSource::Command(cmd) => cmd.updated(state),
+            // State machines are cheap to check, so the case is chosen again
+            ConditionSource::StateMachine(_) => {
+                let case = self.choose(state);
+                state.get::<ConditionalState>(self.instance).last_case != Some(case)
+            }
+        };
+        for (_, cmd) in self.cases.iter() {
+            updated |= cmd.updated(state);
+        }
+        self.default.updated(state) || updated
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+
+impl ConditionalCommand {
+    // Returns index of the first matching case, or cases.len() if none match
+    fn choose(&self, state: &mut CommandSharedState) -> usize {
+        let subject = match &self.source {
+            ConditionSource::Command(cmd) => cmd.execute(state),
+            ConditionSource::StateMachine(m) => state.get::<StateSingleton>(0).current_name(m).unwrap_or_default()
+        };
+
+        self.cases.iter()
+            .position(|(conditions, _)| conditions.iter().all(|c| c.check(&subject)))
+            .unwrap_or(self.cases.len())
+    }
+}
+
+impl Condition {
+    /// Build a list of conditions from command options, an empty output is treated as false when none are given
+    pub fn list(above: Option<f64>, below: Option<f64>, equals: Option<Value>, matches: Option<String>) -> Vec<Self> {
+        let mut v = vec!();
+
+        if let Some(x) = above {v.push(Self::Above(x))}
+        if let Some(x) = below {v.push(Self::Below(x))}
+        match equals {
+            Some(Value::Number(n)) => v.push(Self::EqualsNumber(n.as_f64().unwrap_or(f64::NAN))),
+            Some(Value::String(s)) => v.push(Self::EqualsText(s)),
+            Some(Value::Bool(b)) => v.push(Self::EqualsText(b.to_string())),
+            Some(x) => panic!("'equals' must be either a number or a string, found {:?}", x),
+            None => ()
+        }
+        if let Some(x) = matches {
+            v.push(Self::Matches(Pattern(Regex::new(&x).unwrap_or_else(|e| panic!("Invalid regex '{}': {}", x, e)))))
+        }
+
+        if v.is_empty() {
+            v.push(Self::Matches(Pattern(Regex::new(r"\S").unwrap())))
+        }
+        v
+    }
+
+    fn check(&self, subject: &str) -> bool {
+        let number = || find_human_readable(subject.chars());
+
+        match self {
+            Self::Above(x) => number().map(|n| n > *x).unwrap_or(false),
+            Self::Below(x) => number().map(|n| n < *x).unwrap_or(false),
+            Self::EqualsNumber(x) => number().map(|n| (n - *x).abs() < f64::EPSILON).unwrap_or(false),
+            Self::EqualsText(x) => subject.trim() == x,
+            Self::Matches(r) => r.0.is_match(subject)
+        }
+    }
+}
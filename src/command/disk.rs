@@ -0,0 +1,192 @@
+
+use crate::command::{CommandTrait, CommandSharedState};
+use crate::command::sysinfo::NetInfoTime;
+use crate::utils::human_readable;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use std::fs;
+
+// /proc/diskstats always counts 512-byte sectors, regardless of the device
+const SECTOR_SIZE: u64 = 512;
+
+#[derive(Clone, PartialEq)]
+pub struct DiskIOCommand {
+    pub ty: DiskIOType,
+    pub time: NetInfoTime,
+    pub device: Option<String>,
+    pub mountpoint: Option<String>
+}
+
+#[derive(Clone, PartialEq)]
+pub enum DiskIOType {
+    Read,
+    Write
+}
+
+#[derive(Default)]
+struct DiskStats {
+    read: u64,
+    written: u64,
+    read_since: u64,
+    written_since: u64
+}
+
+#[derive(Default)]
+struct DiskStatsSingleton {
+    stats: HashMap<String, DiskStats>,
+    last_refresh: Option<Instant>,
+    update_time: f64
+}
+
+
+impl CommandTrait for DiskIOCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+
+        let device = match (&self.device, &self.mountpoint) {
+            (Some(d), _) => Some(device_name(Path::new(d))),
+            (None, Some(m)) => match mount_source(&resolve_mountpoint(m)) {
+                Some(src) => Some(device_name(&src)),
+                None => return "ERR".to_string()
+            }
+            (None, None) => None
+        };
+
+        match state.get::<DiskStatsSingleton>(0).io(&device, &self.ty, &self.time) {
+            Some(v) => {
+                let suffix = if let NetInfoTime::PerSecond = self.time {"/s"} else {""};
+                format!("{}B{}", human_readable(v), suffix)
+            }
+            None => "ERR".to_string()
+        }
+    }
+}
+
+
+/// Find the device mounted at a given path, as listed in /proc/mounts
+pub fn mount_source(mountpoint: &Path) -> Option<PathBuf> {
+    let mounts = fs::read_to_string("/proc/mounts").ok()?;
+
+    mounts.lines().rev()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let source = fields.next()?;
+            let target = unescape_mount_path(fields.next()?);
+            Some((PathBuf::from(source), target))
+        })
+        // Later mounts shadow earlier ones
+        .find(|(_, target)| Path::new(target) == mountpoint)
+        .map(|(source, _)| source)
+}
+
+/// Decode octal escapes (e.g. \040 for a space) used in /proc/mounts
+pub fn unescape_mount_path(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let code = chars.by_ref().take(3).collect::<String>();
+            match u8::from_str_radix(&code, 8) {
+                Ok(x) => out.push(x as char),
+                Err(_) => {out.push(c); out.push_str(&code);}
+            }
+        }
+        else {out.push(c)}
+    }
+    out
+}
+
+/// Make a mountpoint absolute - "~" refers to the home directory, other relative paths to "/"
+pub fn resolve_mountpoint(mountpoint: &str) -> PathBuf {
+    let path = if mountpoint == "~" || mountpoint.starts_with("~/") {
+        dirs::home_dir().unwrap_or_default().join(mountpoint[1..].trim_start_matches('/'))
+    }
+    else {Path::new("/").join(mountpoint)};
+
+    fs::canonicalize(&path).unwrap_or(path)
+}
+
+/// Resolve symlinks such as /dev/disk/by-uuid/... or /dev/mapper/..., "sda1" is treated as /dev/sda1
+pub fn device_path(dev: &Path) -> PathBuf {
+    let dev = if dev.is_relative() && !dev.exists() {
+        Path::new("/dev").join(dev)
+    } else {dev.to_path_buf()};
+
+    fs::canonicalize(&dev).unwrap_or(dev)
+}
+
+// Turn /dev/sda1, /dev/mapper/root etc. into a name used by the kernel, such as sda1 or dm-0
+fn device_name(dev: &Path) -> String {
+    device_path(dev).file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+impl DiskStatsSingleton {
+
+    fn refresh(&mut self) {
+        if let Some(i) = self.last_refresh {
+            if i.elapsed().as_millis() <= 30 {
+                return;
+            }
+        }
+        self.update_time = self.last_refresh.unwrap_or_else(Instant::now).elapsed().as_millis() as f64 / 1000.0;
+        self.last_refresh = Some(Instant::now());
+
+        let diskstats = match fs::read_to_string("/proc/diskstats") {
+            Ok(x) => x,
+            Err(_) => return
+        };
+
+        for line in diskstats.lines() {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+
+            if let (Some(name), Some(read), Some(written)) = (fields.get(2), fields.get(5), fields.get(9)) {
+                let read = read.parse::<u64>().unwrap_or(0) * SECTOR_SIZE;
+                let written = written.parse::<u64>().unwrap_or(0) * SECTOR_SIZE;
+
+                let stats = self.stats.entry(name.to_string()).or_insert(DiskStats {read, written, ..Default::default()});
+                stats.read_since = read.saturating_sub(stats.read);
+                stats.written_since = written.saturating_sub(stats.written);
+                stats.read = read;
+                stats.written = written;
+            }
+        }
+    }
+
+    fn io(&mut self, device: &Option<String>, ty: &DiskIOType, time: &NetInfoTime) -> Option<u64> {
+        self.refresh();
+
+        let (mut total, mut present) = (0, false);
+        for (name, stats) in self.stats.iter() {
+            let count = match device {
+                Some(d) => d == name,
+                // Only whole physical disks are summed, since partitions and
+                // device mapper/loop devices are already counted in them
+                None => Path::new("/sys/block").join(name).join("device").exists()
+            };
+
+            if count {
+                total += match (time, ty) {
+                    (NetInfoTime::Total, DiskIOType::Read) => stats.read,
+                    (NetInfoTime::Total, DiskIOType::Write) => stats.written,
+                    (_, DiskIOType::Read) => stats.read_since,
+                    (_, DiskIOType::Write) => stats.written_since
+                };
+                present = true;
+            }
+        }
+
+        if !present {
+            return None;
+        }
+
+        match time {
+            NetInfoTime::PerSecond if self.update_time > 0.0 => Some((total as f64 / self.update_time) as u64),
+            NetInfoTime::PerSecond => Some(0),
+            _ => Some(total)
+        }
+    }
+}
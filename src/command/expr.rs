@@ -0,0 +1,267 @@
+
+use crate::command::{Command, CommandTrait, CommandSharedState};
+use crate::utils::{find_human_readable, human_readable_f64};
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Clone, PartialEq)]
+pub struct ExprCommand {
+    pub expr: Expr,
+    pub vars: Vec<Command>,
+    pub precision: Option<usize>,
+    pub unit: String
+}
+
+/// Parsed arithmetic expression, variables are indices into `ExprCommand::vars`
+#[derive(Clone, PartialEq, Debug)]
+pub enum Expr {
+    Number(f64),
+    Var(usize),
+    Neg(Box<Expr>),
+    BinOp(char, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>)
+}
+
+struct Parser<'a, 'b> {
+    chars: Peekable<Chars<'a>>,
+    names: &'b [String]
+}
+
+
+impl CommandTrait for ExprCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let values = self.vars.iter()
+            .map(|x| find_human_readable(x.execute(state).chars()))
+            .collect::<Option<Vec<_>>>();
+
+        match values.and_then(|v| self.expr.eval(&v)) {
+            Some(x) => match self.precision {
+                Some(p) => format!("{:.*}{}", p, x, self.unit),
+                None => human_readable_f64(x) + self.unit.as_str()
+            }
+            None => "ERR".to_string()
+        }
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        // Every command has to be checked, since checking may also update its state
+        let mut updated = false;
+        for i in self.vars.iter() {
+            updated |= i.updated(state);
+        }
+        updated
+    }
+}
+
+
+impl Expr {
+    /// Parse an expression such as "{a} / ({b} + 2) * 100", `names` lists allowed variables
+    pub fn parse(s: &str, names: &[String]) -> Result<Self, String> {
+        let mut p = Parser {chars: s.chars().peekable(), names};
+        let e = p.expr()?;
+
+        p.skip_whitespace();
+        match p.chars.next() {
+            None => Ok(e),
+            Some(c) => Err(format!("Unexpected '{}'", c))
+        }
+    }
+
+    fn eval(&self, vars: &[f64]) -> Option<f64> {
+        let x = match self {
+            Self::Number(x) => *x,
+            Self::Var(i) => vars[*i],
+            Self::Neg(e) => -e.eval(vars)?,
+            Self::BinOp(op, a, b) => {
+                let (a, b) = (a.eval(vars)?, b.eval(vars)?);
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    '%' => a % b,
+                    _ => a.powf(b)
+                }
+            }
+            Self::Call(f, args) => {
+                let args = args.iter().map(|x| x.eval(vars)).collect::<Option<Vec<_>>>()?;
+                match &f[..] {
+                    "min" => args.iter().cloned().fold(f64::INFINITY, f64::min),
+                    "max" => args.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+                    "abs" => args[0].abs(),
+                    "round" => args[0].round(),
+                    "floor" => args[0].floor(),
+                    _ => args[0].ceil()
+                }
+            }
+        };
+
+        // Division by zero and such shouldn't be displayed as a number
+        if x.is_finite() {Some(x)} else {None}
+    }
+}
+
+impl Parser<'_, '_> {
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            self.chars.next();
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.peek().cloned()
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut e = self.term()?;
+
+        while let Some(op) = self.peek().filter(|c| *c == '+' || *c == '-') {
+            self.chars.next();
+            e = Expr::BinOp(op, Box::new(e), Box::new(self.term()?));
+        }
+        Ok(e)
+    }
+
+    // term := factor (('*' | '/' | '%') factor)*
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut e = self.factor()?;
+
+        while let Some(op) = self.peek().filter(|c| *c == '*' || *c == '/' || *c == '%') {
+            self.chars.next();
+            e = Expr::BinOp(op, Box::new(e), Box::new(self.factor()?));
+        }
+        Ok(e)
+    }
+
+    // factor := '-' factor | atom ('^' factor)?
+    fn factor(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some('-') {
+            self.chars.next();
+            return Ok(Expr::Neg(Box::new(self.factor()?)));
+        }
+
+        let e = self.atom()?;
+
+        if self.peek() == Some('^') {
+            self.chars.next();
+            return Ok(Expr::BinOp('^', Box::new(e), Box::new(self.factor()?)));
+        }
+        Ok(e)
+    }
+
+    // atom := number | '{' name '}' | '(' expr ')' | function '(' expr (',' expr)* ')'
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some('(') => {
+                self.chars.next();
+                let e = self.expr()?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            Some('{') => {
+                self.chars.next();
+                let name = self.take_while(|c| c != '}');
+                self.expect('}')?;
+
+                match self.names.iter().position(|x| *x == name) {
+                    Some(i) => Ok(Expr::Var(i)),
+                    None => Err(format!("Unknown variable '{}'", name))
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let num = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                num.parse::<f64>().map(Expr::Number).map_err(|_| format!("Invalid number '{}'", num))
+            }
+            Some(c) if c.is_alphabetic() => {
+                let name = self.take_while(|c| c.is_alphanumeric());
+                let args_count = match &name[..] {
+                    "min" | "max" => None,
+                    "abs" | "round" | "floor" | "ceil" => Some(1),
+                    _ => return Err(format!("Unknown function '{}'", name))
+                };
+
+                self.expect('(')?;
+                let mut args = vec!(self.expr()?);
+                while self.peek() == Some(',') {
+                    self.chars.next();
+                    args.push(self.expr()?);
+                }
+                self.expect(')')?;
+
+                match args_count {
+                    Some(n) if n != args.len() => Err(format!("Function '{}' takes {} argument(s)", name, n)),
+                    _ => Ok(Expr::Call(name, args))
+                }
+            }
+            Some(c) => Err(format!("Unexpected '{}'", c)),
+            None => Err("Unexpected end of expression".to_string())
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.chars.peek().cloned().filter(|c| f(*c)) {
+            s.push(c);
+            self.chars.next();
+        }
+        s
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.peek() {
+            Some(x) if x == c => {self.chars.next(); Ok(())}
+            Some(x) => Err(format!("Expected '{}', found '{}'", c, x)),
+            None => Err(format!("Expected '{}', found end of expression", c))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Expr;
+
+    fn eval(s: &str) -> Option<f64> {
+        let names = vec!("a".to_string(), "b".to_string());
+        Expr::parse(s, &names).unwrap().eval(&[6.0, 3.0])
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), Some(7.0));
+        assert_eq!(eval("(1 + 2) * 3"), Some(9.0));
+        assert_eq!(eval("10 - 4 - 3"), Some(3.0));
+        assert_eq!(eval("{a} / {b} * 2"), Some(4.0));
+        assert_eq!(eval("7 % 4 + 1"), Some(4.0));
+        assert_eq!(eval("-{a} + 1"), Some(-5.0));
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        assert_eq!(eval("2 ^ 3 ^ 2"), Some(512.0));
+        assert_eq!(eval("2 * 3 ^ 2"), Some(18.0));
+        assert_eq!(eval("-2 ^ 2"), Some(-4.0));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(eval("min({a}, {b}, 4)"), Some(3.0));
+        assert_eq!(eval("max({a}, 10)"), Some(10.0));
+        assert_eq!(eval("round(2.5) + floor(1.7) + ceil(0.2) + abs(-1)"), Some(6.0));
+        assert_eq!(eval("1 / 0"), None);
+    }
+
+    #[test]
+    fn errors() {
+        let names = vec!("a".to_string());
+        assert!(Expr::parse("abs(1, 2)", &names).is_err());
+        assert!(Expr::parse("round()", &names).is_err());
+        assert!(Expr::parse("sqrt(4)", &names).is_err());
+        assert!(Expr::parse("{c} + 1", &names).is_err());
+        assert!(Expr::parse("(1 + 2", &names).is_err());
+        assert!(Expr::parse("1 2", &names).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+
+use crate::command::{CommandTrait, CommandSharedState};
+use crate::command::wakeup::WakeupSingleton;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+
+use inotify::{Inotify, WatchMask};
+
+// How much of an already existing file is read to find its last line
+const TAIL_BACKLOG: u64 = 64 * 1024;
+
+#[derive(Clone, PartialEq)]
+pub struct FileCommand {
+    pub path: PathBuf,
+    pub line: FileLine
+}
+
+#[derive(Clone, PartialEq)]
+pub struct TailCommand(pub PathBuf, pub u64);
+
+/// Which part of a file is displayed - lines are numbered from 1, negative numbers count from the end
+#[derive(Clone, PartialEq)]
+pub enum FileLine {
+    All,
+    Number(i64)
+}
+
+#[derive(Default)]
+struct TailFile {
+    inode: Option<u64>,
+    offset: u64,
+    pending: Vec<u8>,
+    last_line: String,
+    // Watches the file's directory, so that the bar is woken up when it's written to or replaced
+    inotify: Option<Inotify>
+}
+
+#[derive(Default)]
+struct TailSingleton {
+    // By command instance, so that every widget notices new lines
+    files: HashMap<u64, TailFile>
+}
+
+
+impl CommandTrait for FileCommand {
+    fn execute(&self, _state: &mut CommandSharedState) -> String {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(x) => x,
+            Err(_) => return "ERR".to_string()
+        };
+
+        match self.line {
+            FileLine::All => content.trim_end_matches('\n').to_string(),
+            FileLine::Number(n) => {
+                let lines = content.lines().collect::<Vec<_>>();
+                let index = if n < 0 {lines.len() as i64 + n} else {n - 1};
+
+                usize::try_from(index).ok()
+                    .and_then(|i| lines.get(i))
+                    .map(|x| x.to_string())
+                    .unwrap_or_default()
+            }
+        }
+    }
+}
+
+impl CommandTrait for TailCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        self.poll(state);
+        state.get::<TailSingleton>(0).files.entry(self.1).or_default().last_line.clone()
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        self.poll(state)
+    }
+}
+
+impl TailCommand {
+    fn poll(&self, state: &mut CommandSharedState) -> bool {
+        let file = state.get::<TailSingleton>(0).files.entry(self.1).or_default();
+        let changed = file.poll(&self.0);
+
+        if let Some(fd) = file.inotify.as_ref().map(|x| x.as_raw_fd()) {
+            state.get::<WakeupSingleton>(0).watch(fd);
+        }
+        changed
+    }
+}
+
+
+impl TailFile {
+    // Read whatever was appended since the last call, returns true if the last line changed
+    fn poll(&mut self, path: &Path) -> bool {
+        self.watch(path);
+
+        let meta = match fs::metadata(path) {
+            Ok(x) => x,
+            Err(_) => return false
+        };
+
+        // A rotated or truncated file is read again from the beginning
+        match self.inode {
+            None => self.offset = meta.len().saturating_sub(TAIL_BACKLOG),
+            Some(i) if i != meta.ino() || meta.len() < self.offset => {
+                self.offset = 0;
+                self.pending.clear();
+            }
+            _ => ()
+        }
+        self.inode = Some(meta.ino());
+
+        if meta.len() == self.offset {
+            return false;
+        }
+
+        let mut buf = vec!();
+        let read = File::open(path)
+            .and_then(|mut f| {f.seek(SeekFrom::Start(self.offset))?; f.read_to_end(&mut buf)});
+        if read.is_err() {
+            return false;
+        }
+        self.offset += buf.len() as u64;
+        self.pending.extend(buf);
+
+        // Only complete lines are shown, the rest waits for its newline
+        let end = match self.pending.iter().rposition(|x| *x == b'\n') {
+            Some(x) => x,
+            None => return false
+        };
+        let complete = self.pending.drain(..=end).collect::<Vec<_>>();
+
+        match String::from_utf8_lossy(&complete).lines().rev().find(|x| !x.trim().is_empty()) {
+            Some(line) if line != self.last_line => {
+                self.last_line = line.to_string();
+                true
+            }
+            _ => false
+        }
+    }
+
+    // Start watching on the first call, later just discard pending inotify events
+    fn watch(&mut self, path: &Path) {
+        match self.inotify.as_mut() {
+            Some(i) => while i.read_events(&mut [0u8; 4096]).map(|mut x| x.next().is_some()).unwrap_or(false) {},
+            None => {
+                let dir = path.parent().filter(|x| !x.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+                let mask = WatchMask::MODIFY | WatchMask::CLOSE_WRITE | WatchMask::CREATE | WatchMask::MOVED_TO;
+
+                self.inotify = Inotify::init().ok();
+                if let Some(i) = self.inotify.as_mut() {
+                    let _ = i.add_watch(dir, mask);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+
+use crate::command::{CommandTrait, CommandSharedState};
+use crate::command::wakeup::WakeupSingleton;
+use crate::config::resolve_path;
+use crate::utils::LogType;
+
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+use inotify::{Inotify, WatchMask};
+
+#[derive(Clone, PartialEq)]
+pub struct MailUnreadCommand {
+    pub instance: u64,
+    pub paths: Vec<String>
+}
+
+// Mailboxes are counted again this often when none of them could be watched
+const RECOUNT_INTERVAL: Duration = Duration::from_secs(5);
+
+// By command instance, as reading inotify events for one widget would hide them from the others
+#[derive(Default)]
+struct MailState {
+    inotify: Option<Inotify>,
+    // Whether a watch was added to any mailbox on the last count
+    watching: bool,
+    count: Option<usize>,
+    last_count: Option<Instant>
+}
+
+
+impl CommandTrait for MailUnreadCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let s = state.get::<MailState>(self.instance);
+        if s.count.is_none() || !s.watching {
+            s.recount(&self.paths);
+        }
+        let count = s.count.unwrap_or_default();
+
+        wake(state, self.instance);
+        count.to_string()
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        let s = state.get::<MailState>(self.instance);
+        let changed = match s.inotify.as_mut() {
+            Some(i) if s.watching => i.read_events(&mut [0u8; 4096]).map(|mut x| x.next().is_some()).unwrap_or(false),
+            _ => s.last_count.is_none_or(|x| x.elapsed() >= RECOUNT_INTERVAL)
+        };
+
+        let res = if changed {
+            let old = s.count;
+            s.recount(&self.paths);
+            old != s.count
+        }
+        else {false};
+
+        wake(state, self.instance);
+        res
+    }
+}
+
+
+impl MailState {
+    // Count unread messages and watch every matching mailbox, globs are expanded again each time
+    fn recount(&mut self, patterns: &[String]) {
+        if self.inotify.is_none() && self.count.is_none() {
+            self.inotify = Inotify::init()
+                .map_err(|e| {crate::log!(LogType::Warning, "Failed to watch mailboxes: {}", e);})
+                .ok();
+        }
+
+        let mut count = 0;
+        self.watching = false;
+        for path in patterns.iter().flat_map(|x| expand(x)) {
+            let (watched, n) = match maildir_new(&path) {
+                Some(new) => {let n = count_maildir(&new); (new, n)}
+                None => (path.clone(), count_mbox(&path))
+            };
+            count += n;
+
+            // Mbox files may be replaced instead of written to, so the watch is added again on every change
+            let mask = WatchMask::CREATE | WatchMask::DELETE | WatchMask::MOVED_FROM | WatchMask::MOVED_TO
+                     | WatchMask::CLOSE_WRITE | WatchMask::DELETE_SELF | WatchMask::MOVE_SELF;
+            if let Some(i) = self.inotify.as_mut() {
+                self.watching |= i.add_watch(&watched, mask).is_ok();
+            }
+        }
+        self.count = Some(count);
+        self.last_count = Some(Instant::now());
+    }
+}
+
+
+// Refresh on changes to watched mailboxes, or once it's time to count them again
+fn wake(state: &mut CommandSharedState, instance: u64) {
+    let s = state.get::<MailState>(instance);
+    let fd = s.inotify.as_ref().filter(|_| s.watching).map(|x| x.as_raw_fd());
+    let next = s.last_count.unwrap_or_else(Instant::now) + RECOUNT_INTERVAL;
+
+    let wakeup = state.get::<WakeupSingleton>(0);
+    match fd {
+        Some(fd) => wakeup.watch(fd),
+        None => wakeup.at(next)
+    }
+}
+
+
+fn expand(pattern: &str) -> Vec<PathBuf> {
+    let path = resolve_path(pattern);
+    match glob::glob(&path.to_string_lossy()) {
+        Ok(paths) => paths.filter_map(|x| x.ok()).collect(),
+        Err(e) => {crate::log!(LogType::Warning, "Invalid mailbox pattern '{}': {}", pattern, e); vec!()}
+    }
+}
+
+// The "new" directory of a Maildir, given either the Maildir itself or its "new" directory
+fn maildir_new(path: &Path) -> Option<PathBuf> {
+    if !path.is_dir() {
+        None
+    }
+    else if path.file_name().map(|x| x == "new").unwrap_or(false) {
+        Some(path.to_path_buf())
+    }
+    else {
+        Some(path.join("new"))
+    }
+}
+
+fn count_maildir(new: &Path) -> usize {
+    fs::read_dir(new)
+        .map(|dir| dir.filter_map(|x| x.ok()).filter(|x| !x.file_name().to_string_lossy().starts_with('.')).count())
+        .unwrap_or(0)
+}
+
+// Messages in an mbox file without the "R" (read) flag in their Status header
+fn count_mbox(path: &Path) -> usize {
+    let content = match fs::read(path) {
+        Ok(x) => x,
+        Err(_) => return 0
+    };
+
+    let mut count = 0;
+    let mut in_headers = false;
+    let mut read = false;
+    let mut prev_empty = true;
+
+    for line in content.split(|x| *x == b'\n') {
+        if prev_empty && line.starts_with(b"From ") {
+            if in_headers && !read {
+                count += 1;
+            }
+            in_headers = true;
+            read = false;
+        }
+        else if in_headers {
+            if line.is_empty() {
+                if !read {count += 1}
+                in_headers = false;
+            }
+            else if line.starts_with(b"Status:") {
+                read = line[7..].contains(&b'R');
+            }
+        }
+        prev_empty = line.is_empty();
+    }
+
+    if in_headers && !read {count += 1}
+    count
+}
@@ -0,0 +1,248 @@
+
+use crate::command::{CommandTrait, CommandSharedState};
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ffi::CStr;
+use std::path::Path;
+use std::fs;
+use std::ptr;
+
+// Flags from <linux/if.h>, as reported by /sys/class/net/*/flags
+const IFF_UP: u32 = 0x1;
+const IFF_LOOPBACK: u32 = 0x8;
+
+// Wireless extensions ioctl for getting the ESSID, from <linux/wireless.h>
+const SIOCGIWESSID: u64 = 0x8B1B;
+const IW_ESSID_MAX_SIZE: usize = 32;
+
+// Link quality reported by /proc/net/wireless is usually out of 70
+const WIFI_QUALITY_MAX: f32 = 70.0;
+
+#[derive(Clone, PartialEq)]
+pub struct NetAddressCommand {
+    pub name: Option<String>,
+    pub family: Option<AddressFamily>
+}
+#[derive(Clone, PartialEq)]
+pub struct NetStateCommand(pub Option<String>);
+#[derive(Clone, PartialEq)]
+pub struct WifiSSIDCommand(pub Option<String>);
+#[derive(Clone, PartialEq)]
+pub struct WifiQualityCommand(pub Option<String>);
+
+#[derive(Clone, PartialEq)]
+pub enum AddressFamily {
+    IPv4,
+    IPv6
+}
+
+#[repr(C)]
+struct IwPoint {
+    pointer: *mut libc::c_void,
+    length: u16,
+    flags: u16
+}
+
+#[repr(C)]
+struct IwReq {
+    name: [libc::c_char; libc::IFNAMSIZ],
+    essid: IwPoint
+}
+
+
+impl CommandTrait for NetAddressCommand {
+    fn execute(&self, _state: &mut CommandSharedState) -> String {
+        let name = match interface(&self.name) {
+            Some(x) => x,
+            None => return "ERR".to_string()
+        };
+
+        addresses().into_iter()
+            .filter(|(n, _)| *n == name)
+            .map(|(_, addr)| addr)
+            .filter(|addr| match (&self.family, addr) {
+                (Some(AddressFamily::IPv4), IpAddr::V6(_)) => false,
+                (Some(AddressFamily::IPv6), IpAddr::V4(_)) => false,
+                // Link-local addresses are present on nearly every interface, skip them
+                (_, IpAddr::V6(a)) => a.segments()[0] & 0xffc0 != 0xfe80,
+                _ => true
+            })
+            .map(|addr| addr.to_string())
+            .collect::<Vec<_>>().join(" ")
+    }
+}
+
+impl CommandTrait for NetStateCommand {
+    fn execute(&self, _state: &mut CommandSharedState) -> String {
+        let name = match interface(&self.0) {
+            Some(x) => x,
+            None => return "down".to_string()
+        };
+
+        match flags(&name) {
+            None => "ERR",
+            Some(f) if f & IFF_UP == 0 => "down",
+            // Reading carrier of a downed interface fails, so it's checked only here
+            Some(_) => match read_trimmed(Path::new("/sys/class/net").join(&name).join("carrier")).as_deref() {
+                Some("1") => "up",
+                _ => "no carrier"
+            }
+        }.to_string()
+    }
+}
+
+impl CommandTrait for WifiSSIDCommand {
+    fn execute(&self, _state: &mut CommandSharedState) -> String {
+        wifi_interface(&self.0)
+            .and_then(|name| essid(&name))
+            .unwrap_or_default()
+    }
+}
+
+impl CommandTrait for WifiQualityCommand {
+    fn execute(&self, _state: &mut CommandSharedState) -> String {
+        let name = wifi_interface(&self.0);
+
+        wireless_stats().into_iter()
+            .find(|(n, _)| Some(n) == name.as_ref())
+            .map(|(_, quality)| format!("{:.0}%", (quality / WIFI_QUALITY_MAX * 100.0).min(100.0)))
+            .unwrap_or_else(|| "ERR".to_string())
+    }
+}
+
+
+fn read_trimmed(path: impl AsRef<Path>) -> Option<String> {
+    fs::read_to_string(path).ok().map(|x| x.trim().to_string())
+}
+
+fn flags(name: &str) -> Option<u32> {
+    read_trimmed(Path::new("/sys/class/net").join(name).join("flags"))
+        .and_then(|x| u32::from_str_radix(x.trim_start_matches("0x"), 16).ok())
+}
+
+/// Check whether an interface is a loopback one (e.g. "lo")
+pub fn is_loopback(name: &str) -> bool {
+    flags(name).map(|f| f & IFF_LOOPBACK != 0).unwrap_or(name == "lo")
+}
+
+/// Find the interface used by the default route, preferring IPv4 routes
+pub fn default_interface() -> Option<String> {
+
+    // Iface Destination Gateway Flags RefCnt Use Metric Mask ...
+    let v4 = read_trimmed("/proc/net/route").and_then(|routes| {
+        routes.lines().skip(1)
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .filter(|f| f.len() > 7 && f[1] == "00000000" && f[7] == "00000000")
+            .min_by_key(|f| f[6].parse::<u32>().unwrap_or(u32::MAX))
+            .map(|f| f[0].to_string())
+    });
+
+    // Destination PrefixLength Source SourcePrefixLength NextHop Metric RefCnt Use Flags Iface
+    v4.or_else(|| read_trimmed("/proc/net/ipv6_route").and_then(|routes| {
+        routes.lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .filter(|f| f.len() > 9 && f[1] == "00" && f[0].chars().all(|c| c == '0') && !is_loopback(f[9]))
+            .min_by_key(|f| u32::from_str_radix(f[5], 16).unwrap_or(u32::MAX))
+            .map(|f| f[9].to_string())
+    }))
+}
+
+/// Resolve an interface name - none or "default" refers to the default route's interface
+pub fn interface(name: &Option<String>) -> Option<String> {
+    match name.as_deref() {
+        None | Some("default") => default_interface(),
+        Some(n) => Some(n.to_string())
+    }
+}
+
+// Wireless interfaces default to the first one reported by the kernel
+fn wifi_interface(name: &Option<String>) -> Option<String> {
+    match name {
+        Some(_) => interface(name),
+        None => wireless_stats().into_iter().next().map(|(n, _)| n)
+    }
+}
+
+// Interface names and their link quality from /proc/net/wireless
+fn wireless_stats() -> Vec<(String, f32)> {
+    read_trimmed("/proc/net/wireless").map(|stats| {
+        // The first two lines are headers
+        stats.lines().skip(2)
+            .filter_map(|line| {
+                let mut f = line.split_whitespace();
+                let name = f.next()?.trim_end_matches(':').to_string();
+                let quality = f.nth(1)?.trim_end_matches('.').parse::<f32>().ok()?;
+                Some((name, quality))
+            })
+            .collect()
+    }).unwrap_or_default()
+}
+
+// All interface addresses, as reported by getifaddrs(3)
+fn addresses() -> Vec<(String, IpAddr)> {
+    let mut out = vec!();
+    let mut ifap: *mut libc::ifaddrs = ptr::null_mut();
+
+    unsafe {
+        if libc::getifaddrs(&mut ifap) != 0 {
+            return out;
+        }
+
+        let mut cur = ifap;
+        while !cur.is_null() {
+            let ifa = &*cur;
+
+            if !ifa.ifa_addr.is_null() {
+                let name = CStr::from_ptr(ifa.ifa_name).to_string_lossy().into_owned();
+
+                match (*ifa.ifa_addr).sa_family as i32 {
+                    libc::AF_INET => {
+                        let sa = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                        out.push((name, IpAddr::V4(Ipv4Addr::from(u32::from_be(sa.sin_addr.s_addr)))));
+                    }
+                    libc::AF_INET6 => {
+                        let sa = &*(ifa.ifa_addr as *const libc::sockaddr_in6);
+                        out.push((name, IpAddr::V6(Ipv6Addr::from(sa.sin6_addr.s6_addr))));
+                    }
+                    _ => ()
+                }
+            }
+            cur = ifa.ifa_next;
+        }
+
+        libc::freeifaddrs(ifap);
+    }
+    out
+}
+
+// Get the ESSID of a wireless interface using wireless extensions
+fn essid(name: &str) -> Option<String> {
+    if name.len() >= libc::IFNAMSIZ {
+        return None;
+    }
+
+    let mut buf = [0u8; IW_ESSID_MAX_SIZE + 1];
+    let mut req = IwReq {
+        name: [0; libc::IFNAMSIZ],
+        essid: IwPoint {pointer: buf.as_mut_ptr() as *mut _, length: buf.len() as u16, flags: 0}
+    };
+    for (c, i) in name.bytes().enumerate() {
+        req.name[c] = i as libc::c_char;
+    }
+
+    unsafe {
+        let sock = libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0);
+        if sock < 0 {
+            return None;
+        }
+        let res = libc::ioctl(sock, SIOCGIWESSID as _, &mut req as *mut IwReq);
+        libc::close(sock);
+
+        if res < 0 {
+            return None;
+        }
+    }
+
+    let len = (req.essid.length as usize).min(IW_ESSID_MAX_SIZE);
+    Some(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
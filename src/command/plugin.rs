@@ -0,0 +1,120 @@
+
+use crate::command::{CommandTrait, CommandSharedState};
+use crate::config::config_dir;
+use crate::utils::LogType;
+
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_void};
+use std::rc::Rc;
+
+use libloading::Library;
+use serde_yaml::{Value, Mapping};
+
+/// Version of the plugin interface, plugins built for a different one are rejected
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+// Output longer than this is truncated
+const OUTPUT_BUFFER_SIZE: usize = 4096;
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type NewFn = unsafe extern "C" fn(options: *const c_char) -> *mut c_void;
+type ExecuteFn = unsafe extern "C" fn(instance: *mut c_void, buffer: *mut c_char, size: usize) -> c_int;
+type UpdatedFn = unsafe extern "C" fn(instance: *mut c_void) -> c_int;
+type FreeFn = unsafe extern "C" fn(instance: *mut c_void);
+
+#[derive(Clone)]
+pub struct PluginCommand(Rc<PluginInstance>);
+
+struct PluginInstance {
+    name: String,
+    instance: *mut c_void,
+    execute: ExecuteFn,
+    updated: Option<UpdatedFn>,
+    free: Option<FreeFn>,
+    // Has to outlive the functions above, so it's dropped last
+    _library: Library
+}
+
+
+impl CommandTrait for PluginCommand {
+    fn execute(&self, _state: &mut CommandSharedState) -> String {
+        let p = &self.0;
+        let mut buf = vec!(0u8; OUTPUT_BUFFER_SIZE);
+
+        let res = unsafe {(p.execute)(p.instance, buf.as_mut_ptr() as *mut c_char, buf.len())};
+        if res < 0 {
+            crate::log!(LogType::Warning, "Plugin '{}' failed with code {}", p.name, res);
+            return "ERR".to_string();
+        }
+
+        let len = buf.iter().position(|x| *x == 0).unwrap_or(buf.len());
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+    fn updated(&self, _state: &mut CommandSharedState) -> bool {
+        match self.0.updated {
+            Some(f) => unsafe {f(self.0.instance) != 0},
+            None => false
+        }
+    }
+}
+
+impl Drop for PluginInstance {
+    fn drop(&mut self) {
+        if let Some(f) = self.free {
+            unsafe {f(self.instance)}
+        }
+    }
+}
+
+
+impl PluginCommand {
+    /// Load `{name}.so` or `lib{name}.so` from the plugins directory, other options are passed as YAML
+    pub fn new(mut options: Mapping) -> Self {
+        options.remove(&Value::String("type".to_string()));
+        let name = match options.remove(&Value::String("name".to_string())) {
+            Some(Value::String(x)) => x,
+            _ => panic!("'plugin' command requires 'name' to be set")
+        };
+
+        let dir = config_dir().join("plugins");
+        let path = [format!("{}.so", name), format!("lib{}.so", name)].iter()
+            .map(|x| dir.join(x))
+            .find(|x| x.exists())
+            .unwrap_or_else(|| panic!("Plugin '{}' not found in {}", name, dir.display()));
+
+        let options = serde_yaml::to_string(&Value::Mapping(options)).unwrap_or_default();
+        let options = CString::new(options).expect("Plugin options must not contain null characters");
+
+        unsafe {
+            let library = Library::new(&path)
+                .unwrap_or_else(|e| panic!("Failed to load plugin '{}': {}", name, e));
+
+            let missing = |s: &[u8]| -> ! {
+                panic!("Plugin '{}' doesn't export {}", name, String::from_utf8_lossy(&s[..s.len() - 1]))
+            };
+
+            let version = symbol::<AbiVersionFn>(&library, b"ravenbar_plugin_abi_version\0").unwrap_or_else(|| missing(b"ravenbar_plugin_abi_version\0"))();
+            if version != PLUGIN_ABI_VERSION {
+                panic!("Plugin '{}' uses interface version {}, expected {}", name, version, PLUGIN_ABI_VERSION);
+            }
+
+            let new = symbol::<NewFn>(&library, b"ravenbar_plugin_new\0").unwrap_or_else(|| missing(b"ravenbar_plugin_new\0"));
+            let execute = symbol::<ExecuteFn>(&library, b"ravenbar_plugin_execute\0").unwrap_or_else(|| missing(b"ravenbar_plugin_execute\0"));
+            let updated = symbol::<UpdatedFn>(&library, b"ravenbar_plugin_updated\0");
+            let free = symbol::<FreeFn>(&library, b"ravenbar_plugin_free\0");
+
+            let instance = new(options.as_ptr());
+            if instance.is_null() {
+                panic!("Plugin '{}' failed to initialize", name);
+            }
+
+            Self(Rc::new(PluginInstance {name, instance, execute, updated, free, _library: library}))
+        }
+    }
+}
+
+
+// Get a function exported by a plugin, the caller must make sure that `T` matches its signature
+unsafe fn symbol<T: Copy>(library: &Library, name: &[u8]) -> Option<T> {
+    library.get::<T>(name).ok().map(|x| *x)
+}
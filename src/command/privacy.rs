@@ -0,0 +1,68 @@
+
+use crate::command::{CommandTrait, CommandSharedState};
+
+use std::fs;
+
+#[derive(Clone, PartialEq)]
+pub struct PrivacyCommand(pub PrivacyDevice);
+
+#[derive(Clone, PartialEq)]
+pub enum PrivacyDevice {
+    Camera,
+    Microphone,
+    Any
+}
+
+
+impl CommandTrait for PrivacyCommand {
+    fn execute(&self, _state: &mut CommandSharedState) -> String {
+        let mut names = fs::read_dir("/proc").map(|dir| {
+            dir.filter_map(|x| x.ok())
+                .filter(|x| x.file_name().to_string_lossy().chars().all(|c| c.is_ascii_digit()))
+                .filter(|x| self.uses_device(&x.path().join("fd")))
+                .filter_map(|x| fs::read_to_string(x.path().join("comm")).ok())
+                .map(|x| x.trim().to_string())
+                .collect::<Vec<_>>()
+        }).unwrap_or_default();
+
+        names.sort();
+        names.dedup();
+
+        // The count comes first, so that `warn` and `critical` can pick it up
+        match names.len() {
+            0 => "0".to_string(),
+            n => format!("{}: {}", n, names.join(", "))
+        }
+    }
+}
+
+
+impl PrivacyCommand {
+    // Processes of other users can't be inspected without root, those are skipped
+    fn uses_device(&self, fd_dir: &std::path::Path) -> bool {
+        fs::read_dir(fd_dir).map(|fds| {
+            fds.filter_map(|x| x.ok())
+                .filter_map(|x| fs::read_link(x.path()).ok())
+                .any(|target| {
+                    let target = target.to_string_lossy();
+                    match self.0 {
+                        PrivacyDevice::Camera => is_camera(&target),
+                        PrivacyDevice::Microphone => is_microphone(&target),
+                        PrivacyDevice::Any => is_camera(&target) || is_microphone(&target)
+                    }
+                })
+        }).unwrap_or(false)
+    }
+}
+
+
+fn is_camera(path: &str) -> bool {
+    path.starts_with("/dev/video")
+}
+
+// ALSA capture devices are named pcmC{card}D{device}c
+fn is_microphone(path: &str) -> bool {
+    path.strip_prefix("/dev/snd/pcmC")
+        .map(|x| x.ends_with('c') && x.contains('D'))
+        .unwrap_or(false)
+}
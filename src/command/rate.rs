@@ -0,0 +1,142 @@
+
+use crate::command::{Command, CommandTrait, CommandSharedState};
+use crate::utils::{find_human_readable, human_readable_f64};
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Calls closer than this are treated as the same sample (e.g. two widgets with the same command)
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(30);
+
+#[derive(Clone, PartialEq)]
+pub struct RateCommand {
+    pub id: u64,
+    pub of: Command,
+    pub smoothing: f64,
+    pub unit: String
+}
+
+#[derive(Clone, PartialEq)]
+pub struct AverageCommand {
+    pub id: u64,
+    pub of: Command,
+    pub smoothing: f64,
+    pub unit: String
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ExtremumCommand {
+    pub id: u64,
+    pub of: Command,
+    pub window: f32,
+    pub max: bool,
+    pub unit: String
+}
+
+#[derive(Default)]
+struct RateState {
+    last: Option<(Instant, f64)>,
+    rate: Option<f64>
+}
+
+#[derive(Default)]
+struct AverageState {
+    last: Option<Instant>,
+    average: Option<f64>
+}
+
+#[derive(Default)]
+struct ExtremumState {
+    samples: VecDeque<(Instant, f64)>
+}
+
+
+impl CommandTrait for RateCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let value = find_human_readable(self.of.execute(state).chars());
+        let s = state.get::<RateState>(self.id);
+
+        if let (Some(v), Some((time, old))) = (value, s.last) {
+            let elapsed = time.elapsed();
+
+            if elapsed >= MIN_SAMPLE_INTERVAL {
+                let rate = (v - old) / elapsed.as_secs_f64();
+                s.rate = Some(ema(s.rate, rate, self.smoothing));
+                s.last = Some((Instant::now(), v));
+            }
+        }
+        else if let Some(v) = value {
+            s.last = Some((Instant::now(), v));
+        }
+
+        format_value(s.rate, &self.unit)
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        self.of.updated(state)
+    }
+}
+
+impl CommandTrait for AverageCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let value = find_human_readable(self.of.execute(state).chars());
+        let s = state.get::<AverageState>(self.id);
+
+        if let Some(v) = value {
+            if s.last.map(|t| t.elapsed() >= MIN_SAMPLE_INTERVAL).unwrap_or(true) {
+                s.average = Some(ema(s.average, v, self.smoothing));
+                s.last = Some(Instant::now());
+            }
+        }
+
+        format_value(s.average, &self.unit)
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        self.of.updated(state)
+    }
+}
+
+impl CommandTrait for ExtremumCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let value = find_human_readable(self.of.execute(state).chars());
+        let s = state.get::<ExtremumState>(self.id);
+
+        if let Some(v) = value {
+            if s.samples.back().map(|(t,_)| t.elapsed() >= MIN_SAMPLE_INTERVAL).unwrap_or(true) {
+                s.samples.push_back((Instant::now(), v));
+            }
+        }
+
+        let window = Duration::from_secs_f32(self.window);
+        while s.samples.front().map(|(t,_)| t.elapsed() > window).unwrap_or(false) {
+            s.samples.pop_front();
+        }
+
+        let values = s.samples.iter().map(|(_,v)| *v);
+        let result = if self.max {
+            values.fold(None, |acc: Option<f64>, v| Some(acc.map_or(v, |a| a.max(v))))
+        } else {
+            values.fold(None, |acc: Option<f64>, v| Some(acc.map_or(v, |a| a.min(v))))
+        };
+
+        format_value(result, &self.unit)
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        self.of.updated(state)
+    }
+}
+
+
+// Exponential moving average - smoothing of 1.0 means no smoothing at all
+fn ema(old: Option<f64>, new: f64, smoothing: f64) -> f64 {
+    match old {
+        Some(o) => o + (new - o) * smoothing,
+        None => new
+    }
+}
+
+fn format_value(value: Option<f64>, unit: &str) -> String {
+    match value {
+        Some(v) => human_readable_f64(v) + unit,
+        None => String::new()
+    }
+}
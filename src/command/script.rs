@@ -0,0 +1,86 @@
+
+use crate::command::{Command, CommandTrait, CommandSharedState, WidgetOutputs, PointerState};
+use crate::command::state::StateSingleton;
+use crate::command::var::VarSingleton;
+use crate::utils::{find_human_readable, human_readable_f64, LogType};
+
+use std::rc::Rc;
+
+use rhai::{Engine, AST, Scope, Dynamic, Map};
+
+// Scripts running longer than this are stopped, so that they can't freeze the bar
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+#[derive(Clone)]
+pub struct ScriptCommand {
+    engine: Rc<Engine>,
+    ast: Rc<AST>,
+    vars: Vec<(String, Command)>
+}
+
+
+impl CommandTrait for ScriptCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let mut scope = Scope::new();
+
+        for (name, cmd) in self.vars.iter() {
+            scope.push_constant(name.as_str(), cmd.execute(state).trim_end_matches('\n').to_string());
+        }
+
+        let states = state.get::<StateSingleton>(0);
+        let machines = states.machines().cloned().collect::<Vec<_>>();
+        scope.push_constant("states", machines.into_iter()
+            .filter_map(|m| states.current_name(&m).map(|s| (m.into(), Dynamic::from(s))))
+            .collect::<Map>());
+
+        scope.push_constant("variables", state.get::<VarSingleton>(0).all().iter()
+            .map(|(k,v)| (k.into(), Dynamic::from(v.clone())))
+            .collect::<Map>());
+
+        scope.push_constant("widgets", state.get::<WidgetOutputs>(0).outputs.iter()
+            .map(|(k,(_,v))| (k.into(), Dynamic::from(v.clone())))
+            .collect::<Map>());
+
+        // Unit unless the script is run as an action with the pointer over the bar
+        scope.push_constant("pointer", state.get::<PointerState>(0).current
+            .map(|p| Dynamic::from([("x", p.x), ("y", p.y), ("scroll_x", p.scroll_x), ("scroll_y", p.scroll_y)].iter()
+                .map(|(k, v)| ((*k).into(), Dynamic::from(*v)))
+                .collect::<Map>()))
+            .unwrap_or(Dynamic::UNIT));
+
+        match self.engine.eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast) {
+            Ok(x) if x.is_unit() => String::new(),
+            Ok(x) => x.to_string(),
+            Err(e) => {
+                crate::log!(LogType::Warning, "Script failed: {}", e);
+                "ERR".to_string()
+            }
+        }
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        // Every command has to be checked, since checking may also update its state
+        let mut updated = false;
+        for (_, cmd) in self.vars.iter() {
+            updated |= cmd.updated(state);
+        }
+        updated
+    }
+}
+
+
+impl ScriptCommand {
+    /// Compile a script, `vars` are commands whose outputs are available to it as constants
+    pub fn new(code: &str, vars: Vec<(String, Command)>) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        // Find a number in text, the same way `warn` and `critical` do
+        engine.register_fn("num", |s: &str| find_human_readable(s.chars()).map(Dynamic::from).unwrap_or(Dynamic::UNIT));
+        engine.register_fn("human", human_readable_f64);
+        engine.register_fn("human", |x: i64| human_readable_f64(x as f64));
+
+        let ast = engine.compile(code).map_err(|e| e.to_string())?;
+
+        Ok(Self {engine: Rc::new(engine), ast: Rc::new(ast), vars})
+    }
+}
@@ -40,12 +40,12 @@ impl StateSingleton {
              .states.get_mut(state).expect(&format!("No state '{}' in '{}'", state, machine))
     }
 
-    fn next(&mut self, machine: &String, traverse: i32) {
+    pub fn next(&mut self, machine: &String, traverse: i32) {
         self.states.get_mut(machine).or_else(|| {crate::log!(LogType::Warning, "No state machine named '{}'", machine); None})
             .and_then(|x| {x.current = (x.current + traverse) % x.states.len() as i32; Some(())});
     }
 
-    fn set(&mut self, machine: &String, state: &String) {
+    pub fn set(&mut self, machine: &String, state: &String) {
         self.states.get_mut(machine).or_else(|| {crate::log!(LogType::Warning, "No state machine named '{}'", machine); None})
             .and_then(|x| {
                 x.current = *x.states.get(state)
@@ -55,6 +55,15 @@ impl StateSingleton {
             }); Some(())});
     }
 
+    pub fn current_name(&self, machine: &String) -> Option<String> {
+        self.states.get(machine).or_else(|| {crate::log!(LogType::Warning, "No state machine named '{}'", machine); None})
+            .and_then(|x| x.states.iter().find(|(_, id)| **id == x.current).map(|(name, _)| name.clone()))
+    }
+
+    pub fn machines(&self) -> impl Iterator<Item = &String> {
+        self.states.keys()
+    }
+
     pub fn get(&self, machine: &String) -> i32 {
         // "get" here should never fail after singleton + listener initialization
         self.states.get(machine).and_then(|x| Some(x.current)).unwrap_or(-1)
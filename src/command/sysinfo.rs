@@ -1,9 +1,11 @@
 
 use crate::command::{CommandTrait, CommandSharedState};
-use crate::config::config_dir;
+use crate::command::disk::{resolve_mountpoint, device_path};
+use crate::command::netif::{default_interface, is_loopback};
 use crate::utils::{human_readable, human_readable_p10};
 
 use std::time::Instant;
+use std::path::{Path, PathBuf};
 
 use ::sysinfo::{System, SystemExt as _, ProcessorExt as _, NetworkExt, DiskExt as _};
 
@@ -22,7 +24,15 @@ pub struct MemoryInfoCommand {
 pub enum MemoryInfoType {
     RAM,
     Swap,
-    Disk(Option<String>)
+    Disk(DiskSelector)
+}
+
+#[derive(Clone, PartialEq)]
+pub enum DiskSelector {
+    Mountpoint(PathBuf),
+    Device(PathBuf),
+    All,
+    Fullest
 }
 
 #[derive(Clone, PartialEq)]
@@ -87,16 +97,53 @@ impl CommandTrait for CPUFreqCommand {
 
 impl CommandTrait for MemoryInfoCommand {
     fn execute(&self, state: &mut CommandSharedState) -> String {
-        if let Some((usage, total)) = state.get::<SystemSingleton>(0).mem(&self.ty) {
+        match &self.ty {
+            MemoryInfoType::Disk(sel) => {
+                let disks = state.get::<SystemSingleton>(0).disks(sel);
 
-            match self.val {
-                MemoryInfoValue::Total => human_readable(total) + "B",
-                MemoryInfoValue::Usage => human_readable(usage) + "B",
-                MemoryInfoValue::Free => human_readable(total - usage) + "B",
-                MemoryInfoValue::Percent => format!("{:.0}%", usage as f64 / total as f64 * 100.0),
+                if disks.is_empty() {
+                    return "ERR".to_string();
+                }
+
+                match sel {
+                    // Show which disk the value refers to
+                    DiskSelector::All | DiskSelector::Fullest => disks.iter()
+                        .map(|(mnt, usage, total)| format!("{} {}", mnt.display(), self.val.format(*usage, *total)))
+                        .collect::<Vec<_>>().join(" "),
+                    _ => self.val.format(disks[0].1, disks[0].2)
+                }
+            }
+            ty => if let Some((usage, total)) = state.get::<SystemSingleton>(0).mem(ty) {
+                self.val.format(usage, total)
             }
+            else {"ERR".to_string()}
+        }
+    }
+}
+
+impl MemoryInfoValue {
+    fn format(&self, usage: u64, total: u64) -> String {
+        match self {
+            MemoryInfoValue::Total => human_readable(total) + "B",
+            MemoryInfoValue::Usage => human_readable(usage) + "B",
+            MemoryInfoValue::Free => human_readable(total - usage) + "B",
+            MemoryInfoValue::Percent => format!("{:.0}%", usage as f64 / total as f64 * 100.0),
+        }
+    }
+}
+
+impl DiskSelector {
+    pub fn new(mountpoint: Option<String>, device: Option<String>, label: Option<String>, uuid: Option<String>, select: Option<String>) -> Self {
+        match (select.as_deref(), mountpoint, device, label, uuid) {
+            (Some("all"), ..) => Self::All,
+            (Some("fullest"), ..) => Self::Fullest,
+            (Some(s), ..) => panic!("Unknown disk selection '{}' (expected 'all' or 'fullest')", s),
+            (None, Some(m), ..) => Self::Mountpoint(resolve_mountpoint(&m)),
+            (None, None, Some(d), ..) => Self::Device(PathBuf::from(d)),
+            (None, None, None, Some(l), _) => Self::Device(Path::new("/dev/disk/by-label").join(l)),
+            (None, None, None, None, Some(u)) => Self::Device(Path::new("/dev/disk/by-uuid").join(u)),
+            _ => Self::Mountpoint(PathBuf::from("/"))
         }
-        else {"ERR".to_string()}
     }
 }
 
@@ -142,7 +189,8 @@ impl SystemSingleton {
         refresh!(self.last_mem, self.system.refresh_memory())
     }
     fn refresh_disks(&mut self) {
-        refresh!(self.last_disks, self.system.refresh_disks())
+        // Refresh the whole list, since filesystems may be mounted/unmounted at any time
+        refresh!(self.last_disks, self.system.refresh_disks_list())
     }
     fn refresh_net(&mut self) {
         if let None = self.last_net {
@@ -186,20 +234,36 @@ impl SystemSingleton {
                 Some((self.system.get_used_swap() * 1000, self.system.get_total_swap() * 1000))
 
             }
-            MemoryInfoType::Disk(mnt) => {
-                self.refresh_disks();
-                if let None = mnt {
-                    return None;
-                }
+            MemoryInfoType::Disk(_) => None
+        }
+    }
 
-                for i in self.system.get_disks() {
-                    if i.get_mount_point() == config_dir().join(mnt.as_ref().unwrap()) {
-                        return Some((i.get_total_space() - i.get_available_space(), i.get_total_space()));
-                    }
-                }
-                None
+    fn disks(&mut self, sel: &DiskSelector) -> Vec<(PathBuf, u64, u64)> {
+        self.refresh_disks();
+
+        let disks = self.system.get_disks().iter()
+            .map(|i| (i, i.get_total_space() - i.get_available_space(), i.get_total_space()));
+
+        let selected: Vec<_> = match sel {
+            // Later mounts shadow earlier ones
+            DiskSelector::Mountpoint(mnt) => disks.rev()
+                .find(|(i,_,_)| i.get_mount_point() == mnt)
+                .into_iter().collect(),
+            DiskSelector::Device(dev) => {
+                let dev = device_path(dev);
+                disks.filter(|(i,_,_)| device_path(Path::new(i.get_name())) == dev)
+                    .take(1).collect()
             }
-        }
+            DiskSelector::All => disks.collect(),
+            DiskSelector::Fullest => disks
+                .filter(|(_,_,total)| *total != 0)
+                .max_by(|(_,u1,t1), (_,u2,t2)| (*u1 as f64 / *t1 as f64).partial_cmp(&(*u2 as f64 / *t2 as f64)).unwrap())
+                .into_iter().collect()
+        };
+
+        selected.into_iter()
+            .map(|(i, usage, total)| (i.get_mount_point().to_path_buf(), usage, total))
+            .collect()
     }
 
     fn net(&mut self, name: &Option<String>, ty: &NetInfoType, time: &NetInfoTime, val: &NetInfoValue) -> Option<u64> {
@@ -214,11 +278,16 @@ impl SystemSingleton {
         // Multiplier for bits
         let bitmul = if let NetInfoValue::Bits = val {8} else {1};
 
+        let name = match name.as_deref() {
+            Some("default") => default_interface(),
+            _ => name.clone()
+        };
+
         let (mut total, mut present) = (0, false);
         for (netname, network) in self.system.get_networks() {
-            let count = match name {
+            let count = match &name {
                 Some(n) => n == netname,
-                None => true
+                None => !is_loopback(netname)
             };
 
             if count {
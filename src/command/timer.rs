@@ -0,0 +1,275 @@
+
+use crate::command::{CommandTrait, CommandSharedState};
+use crate::command::state::StateSingleton;
+use crate::command::wakeup::WakeupSingleton;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, PartialEq)]
+pub struct TimerCommand {
+    pub instance: u64,
+    pub name: String,
+    pub duration: TimerDuration,
+    pub state_machine: Option<String>,
+    pub state: Option<String>
+}
+
+#[derive(Clone, PartialEq)]
+pub struct StopwatchCommand {
+    pub instance: u64,
+    pub name: String
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ClockControlCommand(pub String, pub ClockControl);
+
+/// Countdown length, either fixed or chosen by the current state of the timer's state machine
+#[derive(Clone, PartialEq)]
+pub enum TimerDuration {
+    Fixed(Duration),
+    PerState(HashMap<String, Duration>)
+}
+
+#[derive(Clone, PartialEq)]
+pub enum ClockControl {
+    Start,
+    Pause,
+    Toggle,
+    Reset
+}
+
+#[derive(Default)]
+struct Clock {
+    elapsed: Duration,
+    started: Option<Instant>,
+    finished: bool
+}
+
+// Clocks shared by timers, stopwatches and their controls, keyed by kind and name such as "timer.default"
+#[derive(Default)]
+struct ClockSingleton {
+    clocks: HashMap<String, Clock>
+}
+
+// Last text displayed by a command instance, used to report an update on each tick
+#[derive(Default)]
+struct ClockDisplayState {
+    last: String
+}
+
+
+impl CommandTrait for TimerCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let text = self.tick(state);
+        state.get::<ClockDisplayState>(self.instance).last = text.clone();
+        text
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        let text = self.tick(state);
+        state.get::<ClockDisplayState>(self.instance).last != text
+    }
+}
+
+impl CommandTrait for StopwatchCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let text = self.tick(state);
+        state.get::<ClockDisplayState>(self.instance).last = text.clone();
+        text
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        let text = self.tick(state);
+        state.get::<ClockDisplayState>(self.instance).last != text
+    }
+}
+
+impl CommandTrait for ClockControlCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let c = clock(state, &self.0);
+
+        match self.1 {
+            ClockControl::Start => c.start(),
+            ClockControl::Pause => c.pause(),
+            ClockControl::Toggle => if c.started.is_some() {c.pause()} else {c.start()},
+            ClockControl::Reset => *c = Clock::default()
+        }
+        String::new()
+    }
+}
+
+
+impl TimerCommand {
+    // Check whether the countdown has finished and return the remaining time
+    fn tick(&self, state: &mut CommandSharedState) -> String {
+        let duration = match self.current_duration(state) {
+            Some(x) => x,
+            None => return self.stop(state)
+        };
+        let c = clock(state, &self.name);
+
+        if c.finished || c.started.is_none() || c.elapsed() < duration {
+            let elapsed = c.elapsed();
+            if c.started.is_some() {
+                wake_on_change(state, duration.saturating_sub(elapsed));
+            }
+            return format_duration(remaining(duration, elapsed));
+        }
+
+        // Per-state timers carry on with the next state's duration, others stop
+        match self.duration {
+            TimerDuration::PerState(_) => *c = Clock {elapsed: Duration::ZERO, started: Some(Instant::now()), finished: false},
+            TimerDuration::Fixed(_) => *c = Clock {elapsed: duration, started: None, finished: true}
+        }
+
+        if let Some(sm) = &self.state_machine {
+            let s = state.get::<StateSingleton>(0);
+            match &self.state {
+                Some(x) => s.set(sm, x),
+                None => s.next(sm, 1)
+            }
+        }
+
+        let duration = match self.current_duration(state) {
+            Some(x) => x,
+            None => return self.stop(state)
+        };
+        let c = clock(state, &self.name);
+        let (elapsed, running) = (c.elapsed(), c.started.is_some());
+        if running {
+            wake_on_change(state, duration.saturating_sub(elapsed));
+        }
+        format_duration(remaining(duration, elapsed))
+    }
+
+    // States without a duration stop the timer, just like a fixed countdown finishing
+    fn stop(&self, state: &mut CommandSharedState) -> String {
+        let c = clock(state, &self.name);
+        if c.started.is_some() {
+            *c = Clock {elapsed: c.elapsed(), started: None, finished: true};
+        }
+        format_duration(Duration::ZERO)
+    }
+
+    fn current_duration(&self, state: &mut CommandSharedState) -> Option<Duration> {
+        match &self.duration {
+            TimerDuration::Fixed(d) => Some(*d),
+            TimerDuration::PerState(m) => self.state_machine.as_ref()
+                .and_then(|sm| state.get::<StateSingleton>(0).current_name(sm))
+                .and_then(|s| m.get(&s).cloned())
+        }
+    }
+}
+
+impl StopwatchCommand {
+    fn tick(&self, state: &mut CommandSharedState) -> String {
+        let c = clock(state, &self.name);
+        let (elapsed, running) = (c.elapsed(), c.started.is_some());
+        if running {
+            wake_on_change(state, Duration::from_secs(1) - Duration::from_nanos(elapsed.subsec_nanos().into()));
+        }
+        format_duration(elapsed)
+    }
+}
+
+impl Clock {
+    fn elapsed(&self) -> Duration {
+        self.elapsed + self.started.map(|x| x.elapsed()).unwrap_or_default()
+    }
+
+    fn start(&mut self) {
+        // Starting a finished countdown starts it over
+        if self.finished {
+            *self = Self::default();
+        }
+        if self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+    }
+
+    fn pause(&mut self) {
+        self.elapsed = self.elapsed();
+        self.started = None;
+    }
+}
+
+impl TimerDuration {
+    /// Parse a duration such as "90", "25m" or "1h 30m 15s", plain numbers are seconds
+    pub fn parse(s: &str) -> Result<Duration, String> {
+        let mut total = 0.0;
+        let mut number = String::new();
+
+        for c in s.chars().filter(|c| !c.is_whitespace()) {
+            let unit = match c {
+                'h' => 3600.0,
+                'm' => 60.0,
+                's' => 1.0,
+                _ if c.is_ascii_digit() || c == '.' => {number.push(c); continue}
+                _ => return Err(format!("Unexpected '{}' in duration '{}'", c, s))
+            };
+            total += number.parse::<f64>().map_err(|_| format!("Invalid duration '{}'", s))? * unit;
+            number.clear();
+        }
+        if !number.is_empty() {
+            total += number.parse::<f64>().map_err(|_| format!("Invalid duration '{}'", s))?;
+        }
+
+        if total <= 0.0 {
+            return Err(format!("Duration '{}' must be longer than 0", s));
+        }
+        Ok(Duration::from_secs_f64(total))
+    }
+}
+
+
+fn clock<'a>(state: &'a mut CommandSharedState, name: &str) -> &'a mut Clock {
+    state.get::<ClockSingleton>(0).clocks.entry(name.to_string()).or_default()
+}
+
+// Refresh when the displayed time changes, that is when `left` reaches a full second
+fn wake_on_change(state: &mut CommandSharedState, left: Duration) {
+    let next = match left.subsec_nanos() {
+        0 => Duration::from_secs(1),
+        x => Duration::from_nanos(x.into())
+    };
+    state.get::<WakeupSingleton>(0).at(Instant::now() + next);
+}
+
+// Rounded up, so that a countdown shows its full length when started and 0:00 only when finished
+fn remaining(duration: Duration, elapsed: Duration) -> Duration {
+    let d = duration.saturating_sub(elapsed);
+    Duration::from_secs(d.as_secs() + (d.subsec_nanos() > 0) as u64)
+}
+
+// Format as M:SS, or H:MM:SS for an hour and more
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match secs / 3600 {
+        0 => format!("{}:{:02}", secs / 60, secs % 60),
+        h => format!("{}:{:02}:{:02}", h, secs / 60 % 60, secs % 60)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::TimerDuration;
+    use std::time::Duration;
+
+    #[test]
+    fn duration_units() {
+        assert_eq!(TimerDuration::parse("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(TimerDuration::parse("25m"), Ok(Duration::from_secs(25 * 60)));
+        assert_eq!(TimerDuration::parse("1h 30m 15s"), Ok(Duration::from_secs(5415)));
+        assert_eq!(TimerDuration::parse("1m30"), Ok(Duration::from_secs(90)));
+        assert_eq!(TimerDuration::parse("1.5m"), Ok(Duration::from_secs(90)));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        assert!(TimerDuration::parse("0").is_err());
+        assert!(TimerDuration::parse("0m 0s").is_err());
+        assert!(TimerDuration::parse("").is_err());
+        assert!(TimerDuration::parse("5d").is_err());
+        assert!(TimerDuration::parse("1..5s").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+
+use crate::command::{Command, CommandTrait, CommandSharedState};
+use crate::utils::LogType;
+
+use std::collections::HashMap;
+
+#[derive(Clone, PartialEq)]
+pub struct VarGetCommand {
+    pub instance: u64,
+    pub name: String
+}
+
+#[derive(Clone, PartialEq)]
+pub struct VarSetCommand(pub String, pub VarValue);
+
+#[derive(Clone, PartialEq)]
+pub struct VarAppendCommand(pub String, pub VarValue);
+
+#[derive(Clone, PartialEq)]
+pub struct VarIncCommand {
+    pub name: String,
+    pub by: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>
+}
+
+/// A value assigned to a variable, either given directly or taken from a command's output
+#[derive(Clone, PartialEq)]
+pub enum VarValue {
+    Text(String),
+    Command(Command)
+}
+
+#[derive(Default)]
+pub struct VarSingleton {
+    vars: HashMap<String, String>
+}
+
+// Last value returned by a var_get instance, used to report updates
+#[derive(Default)]
+struct VarGetState {
+    last: Option<String>
+}
+
+
+impl CommandTrait for VarGetCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let value = state.get::<VarSingleton>(0).get(&self.name);
+        state.get::<VarGetState>(self.instance).last = Some(value.clone());
+        value
+    }
+    fn updated(&self, state: &mut CommandSharedState) -> bool {
+        let value = state.get::<VarSingleton>(0).get(&self.name);
+        state.get::<VarGetState>(self.instance).last.as_ref() != Some(&value)
+    }
+}
+
+impl CommandTrait for VarSetCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let value = self.1.get(state);
+        state.get::<VarSingleton>(0).set(&self.0, value);
+        String::new()
+    }
+}
+
+impl CommandTrait for VarAppendCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let value = self.1.get(state);
+        let vars = state.get::<VarSingleton>(0);
+        let new = vars.get(&self.0) + value.as_str();
+        vars.set(&self.0, new);
+        String::new()
+    }
+}
+
+impl CommandTrait for VarIncCommand {
+    fn execute(&self, state: &mut CommandSharedState) -> String {
+        let vars = state.get::<VarSingleton>(0);
+        let old = vars.get(&self.name);
+
+        let old = match (old.trim().is_empty(), old.trim().parse::<f64>()) {
+            (true, _) => 0.0,
+            (false, Ok(x)) => x,
+            (false, Err(_)) => {
+                crate::log!(LogType::Warning, "Variable '{}' is not a number: '{}'", self.name, old);
+                return String::new();
+            }
+        };
+
+        let mut new = old + self.by;
+        if let Some(x) = self.min {new = new.max(x)}
+        if let Some(x) = self.max {new = new.min(x)}
+
+        vars.set(&self.name, new.to_string());
+        String::new()
+    }
+}
+
+
+impl VarValue {
+    fn get(&self, state: &mut CommandSharedState) -> String {
+        match self {
+            Self::Text(x) => x.clone(),
+            Self::Command(cmd) => cmd.execute(state).trim_end_matches('\n').to_string()
+        }
+    }
+}
+
+impl VarSingleton {
+    pub fn initialize(&mut self, vars: &HashMap<String, String>) {
+        self.vars = vars.clone();
+    }
+
+    /// Value of a variable, unset variables are empty
+    pub fn get(&self, name: &str) -> String {
+        self.vars.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn all(&self) -> &HashMap<String, String> {
+        &self.vars
+    }
+
+    fn set(&mut self, name: &str, value: String) {
+        self.vars.insert(name.to_string(), value);
+    }
+}
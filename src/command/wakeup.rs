@@ -0,0 +1,60 @@
+
+use std::collections::HashSet;
+use std::os::unix::io::RawFd;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+static NOTIFY_FD: OnceLock<RawFd> = OnceLock::new();
+
+/// Tells the main loop when commands have to be checked again - either at a given time or when a descriptor becomes readable
+#[derive(Default)]
+pub struct WakeupSingleton {
+    deadline: Option<Instant>,
+    fds: HashSet<RawFd>
+}
+
+
+impl WakeupSingleton {
+    /// Request a refresh at `time`, deadlines have to be requested again after each refresh
+    pub fn at(&mut self, time: Instant) {
+        self.deadline = Some(self.deadline.map_or(time, |x| x.min(time)));
+    }
+
+    /// Refresh whenever `fd` becomes readable, the command is responsible for reading it.
+    /// Like deadlines, descriptors have to be watched again after each refresh
+    pub fn watch(&mut self, fd: RawFd) {
+        self.fds.insert(fd);
+    }
+
+    pub fn take_deadline(&mut self) -> Option<Instant> {
+        self.deadline.take()
+    }
+
+    pub fn take_fds(&mut self) -> Vec<RawFd> {
+        self.fds.drain().collect()
+    }
+}
+
+
+/// Wake the main loop up from another thread
+pub fn notify() {
+    let one = 1u64;
+    unsafe {libc::write(notify_fd(), &one as *const u64 as *const libc::c_void, 8);}
+}
+
+/// Eventfd written to by `notify`
+pub fn notify_fd() -> RawFd {
+    *NOTIFY_FD.get_or_init(|| {
+        let fd = unsafe {libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC)};
+        if fd < 0 {
+            panic!("Failed to create eventfd: {}", std::io::Error::last_os_error());
+        }
+        fd
+    })
+}
+
+/// Reset the eventfd after the main loop woke up
+pub fn clear() {
+    let mut buf = 0u64;
+    unsafe {libc::read(notify_fd(), &mut buf as *mut u64 as *mut libc::c_void, 8);}
+}
@@ -19,6 +19,16 @@ pub fn config_dir<'a>() -> std::path::PathBuf {
     }
 }
 
+/// Resolve a file path - "~" refers to the home directory, other relative paths to the config directory
+pub fn resolve_path(path: &str) -> PathBuf {
+    if path == "~" || path.starts_with("~/") {
+        dirs::home_dir().unwrap_or_default().join(path[1..].trim_start_matches('/'))
+    }
+    else {
+        config_dir().join(path)
+    }
+}
+
 pub fn write_default_config(file: PathBuf) -> Result<(), Box<dyn Error>> {
     let mut cfg = OpenOptions::new()
         .write(true)
@@ -43,7 +53,8 @@ pub struct BarConfig {
     pub default_bg: String,
 
     pub fonts: HashMap<String, Vec<String>>,
-    pub states: HashMap<String, Vec<String>>
+    pub states: HashMap<String, Vec<String>>,
+    pub vars: HashMap<String, String>
 }
 
 
@@ -58,6 +69,7 @@ impl BarConfig {
         let mut fonts = HashMap::<String, Vec<String>>::new();
         let mut templates = HashMap::<String, BarConfigWidget>::new();
         let mut states = HashMap::<String, Vec<String>>::new();
+        let mut vars = HashMap::<String, String>::new();
         
         // Insert the default font
         fonts.insert("default".to_string(), vec!("Monospace".to_string()));
@@ -128,6 +140,15 @@ impl BarConfig {
                         }
                         else {panic!("'font' must be either a string or an array of strings")}
                     }
+                    "var" => {
+                        let v = match val {
+                            Value::String(x) => x.clone(),
+                            Value::Number(x) => x.to_string(),
+                            Value::Bool(x) => x.to_string(),
+                            _ => panic!("'var.{}' must be either a string or a number", event)
+                        };
+                        vars.insert(event.clone(), v);
+                    }
                     _ => {
                         bar_properties_proto.entry((event, settings)).or_default().insert(property.yaml_key(), val.to_owned());
                     }
@@ -183,7 +204,7 @@ impl BarConfig {
             None => "#222233".to_string()
         };
 
-        Ok(BarConfig {properties, widgets_left, widgets_right, fonts, default_bg, states})
+        Ok(BarConfig {properties, widgets_left, widgets_right, fonts, default_bg, states, vars})
     }
 }
 
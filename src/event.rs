@@ -5,13 +5,20 @@ use crate::command::CommandSharedState;
 use std::fmt::Debug;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::os::unix::io::RawFd;
+use std::time::Instant;
 
 use dyn_clone::DynClone;
 
 mod files;
 mod window;
+mod keys;
+
+pub use window::{HoverState, drag_events};
 mod default;
 mod state;
+mod var;
+mod time;
 
 
 pub type Event = Box<dyn EventTrait>;
@@ -24,6 +31,11 @@ pub trait EventTrait: HashedSimple + DynClone + Debug {
     fn precedence(&self) -> u32;
     fn mouse_dependent(&self) -> bool;
     fn is_expose(&self) -> bool;
+
+    /// Hover events are added separately for each bar and widget
+    fn is_hover(&self) -> bool {
+        false
+    }
 }
 
 dyn_clone::clone_trait_object!(EventTrait);
@@ -38,6 +50,16 @@ pub trait EventListener {
     
     /// Add events to the event vector
     fn get(&mut self, bar: &Bar, v: &mut Vec<Event>);
+
+    /// Descriptors which wake the main loop up when they become readable
+    fn fds(&self) -> Vec<RawFd> {
+        vec!()
+    }
+
+    /// Time at which reported events may change, if they can change without any descriptor becoming readable
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
 }
 
 pub struct EventListeners {
@@ -76,6 +98,18 @@ macro_rules! impl_hashed_simple(($type:ty, $id:expr) => {
 });
 
 
+/// Events with hover and drag events of a single bar or widget added, `None` if there are none to add
+pub fn with_local(events: &[Event], local: Vec<Event>) -> Option<Vec<Event>> {
+    if local.is_empty() {
+        return None;
+    }
+    let mut v = events.to_vec();
+    v.extend(local);
+    v.sort_by_key(|x| x.precedence());
+    Some(v)
+}
+
+
 impl Default for Event {
     fn default() -> Self {
         Box::new(default::DefaultEvent)
@@ -90,6 +124,8 @@ impl EventListeners {
             Box::new(files::FilesListener::new()),
             Box::new(window::WindowListener::new()),
             Box::new(state::StateListener::new()),
+            Box::new(var::VarListener::new()),
+            Box::new(time::TimeListener::new()),
             Box::new(default::DefaultListener)
         ];
 
@@ -120,5 +156,13 @@ impl EventListeners {
         v.sort_by_key(|x| x.precedence());
         v
     }
+
+    pub fn fds(&self) -> Vec<RawFd> {
+        self.listeners.iter().flat_map(|x| x.fds()).collect()
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.listeners.iter().filter_map(|x| x.deadline()).min()
+    }
 }
 
@@ -1,17 +1,35 @@
-
 use super::{Event, EventTrait, EventListener};
-use crate::config::config_dir;
+use crate::config::resolve_path;
 use crate::bar::Bar;
+use crate::utils::LogType;
+
+use std::time::{Duration, Instant};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::os::unix::io::{AsRawFd, RawFd};
 
-use std::time::SystemTime;
-use std::path::PathBuf;
+use inotify::{Inotify, WatchDescriptor, WatchMask};
+use glob::Pattern;
+
+// Directories that didn't exist yet are looked for again this often
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
 
 
 #[derive(Debug, Clone, Hash)]
 struct FileChanged(PathBuf);
 
+struct WatchedFile {
+    path: PathBuf,
+    pattern: Pattern
+}
+
 pub struct FilesListener {
-    files_to_watch: Vec<(PathBuf, SystemTime)>
+    inotify: Option<Inotify>,
+    files: Vec<WatchedFile>,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    last_scan: Option<Instant>,
+    // Whether a file's parent directory didn't exist on the last scan
+    missing: bool
 }
 
 
@@ -26,7 +44,31 @@ crate::impl_hashed_simple!(FileChanged, 100010);
 
 impl FilesListener {
     pub fn new() -> Self {
-        Self {files_to_watch: vec!()}
+        Self {inotify: None, files: vec!(), dirs: HashMap::new(), last_scan: None, missing: false}
+    }
+
+    // Watch parent directories of watched files, so that missing and replaced files are handled as well.
+    // Watched directories are watched themselves too, to report changes of their contents
+    fn scan(&mut self) {
+        let inotify = match self.inotify.as_mut() {
+            Some(x) => x,
+            None => return
+        };
+        let mask = WatchMask::CREATE | WatchMask::MODIFY | WatchMask::CLOSE_WRITE | WatchMask::ATTRIB
+                 | WatchMask::DELETE | WatchMask::MOVED_FROM | WatchMask::MOVED_TO;
+
+        self.missing = false;
+        for f in self.files.iter() {
+            let parents = f.path.parent().map(glob_dirs).unwrap_or_default();
+            self.missing |= parents.is_empty();
+
+            for dir in parents.into_iter().chain(glob_dirs(&f.path)) {
+                if let Ok(wd) = inotify.add_watch(&dir, mask) {
+                    self.dirs.insert(wd, dir);
+                }
+            }
+        }
+        self.last_scan = Some(Instant::now());
     }
 }
 
@@ -40,26 +82,90 @@ impl EventListener for FilesListener {
     fn event(&mut self, _cmd: &mut crate::command::CommandSharedState, event: &String, settings: &String) -> Event {
         match &event[..] {
             "on_file_changed" => {
-                let dir = config_dir().join(settings);
+                let path = resolve_path(settings);
+                let pattern = Pattern::new(&path.to_string_lossy())
+                    .unwrap_or_else(|e| panic!("Invalid file pattern '{}': {}", settings, e));
 
-                self.files_to_watch.push((dir.clone(), SystemTime::now()));
-                Box::new(FileChanged(dir))
+                if self.inotify.is_none() {
+                    self.inotify = Inotify::init()
+                        .map_err(|e| {crate::log!(LogType::Error, "Failed to watch files: {}", e);})
+                        .ok();
+                }
+
+                self.files.push(WatchedFile {path: path.clone(), pattern});
+                self.scan();
+                Box::new(FileChanged(path))
             },
             _ => panic!("Unknown event {}.{} (reported by FilesListener)", event, settings)
         }
     }
     fn get(&mut self, _bar: &Bar, v: &mut Vec<Event>) {
-        
-        // TODO migrate to inotify
-        for (file, time) in self.files_to_watch.iter_mut() {
-            let newtime = std::fs::metadata(file as &PathBuf).expect("File not found")
-                .modified().expect("Could not get file modification time");
-
-            if newtime > *time {
-                *time = newtime;
-                v.push(Box::new(FileChanged(file.clone())))
+        for path in self.changed_files() {
+            v.push(Box::new(FileChanged(path)))
+        }
+    }
+    fn fds(&self) -> Vec<RawFd> {
+        self.inotify.iter().map(|x| x.as_raw_fd()).collect()
+    }
+    fn deadline(&self) -> Option<Instant> {
+        // Missing directories won't make inotify readable when they're created
+        self.last_scan.filter(|_| self.missing && self.inotify.is_some()).map(|x| x + RESCAN_INTERVAL)
+    }
+}
+
+impl FilesListener {
+    // Read pending inotify events and return watched paths that have changed
+    fn changed_files(&mut self) -> Vec<PathBuf> {
+        let inotify = match self.inotify.as_mut() {
+            Some(x) => x,
+            None => return vec!()
+        };
+
+        let mut changed = vec!(false; self.files.len());
+        let mut any = false;
+        let mut buffer = [0u8; 4096];
+
+        while let Ok(events) = inotify.read_events(&mut buffer) {
+            let mut empty = true;
+
+            for e in events {
+                empty = false;
+                let dir = match self.dirs.get(&e.wd) {
+                    Some(x) => x,
+                    None => continue
+                };
+                let path = match e.name {
+                    Some(name) => dir.join(name),
+                    None => dir.clone()
+                };
+
+                // Either the file itself or something inside a watched directory has changed
+                for (c, f) in self.files.iter().enumerate() {
+                    if f.pattern.matches_path(&path) || path.parent().map(|x| f.pattern.matches_path(x)).unwrap_or(false) {
+                        changed[c] = true;
+                    }
+                }
             }
+            if empty {break}
+            any = true;
+        }
+
+        // New files or directories may have appeared, which have to be watched as well
+        if any || self.last_scan.is_none_or(|x| x.elapsed() >= RESCAN_INTERVAL) {
+            self.scan();
         }
+
+        self.files.iter().zip(changed)
+            .filter(|(_, c)| *c)
+            .map(|(f, _)| f.path.clone())
+            .collect()
     }
 }
 
+
+// Existing directories matching a pattern
+fn glob_dirs(pattern: &Path) -> Vec<PathBuf> {
+    glob::glob(&pattern.to_string_lossy())
+        .map(|paths| paths.filter_map(|x| x.ok()).filter(|x| x.is_dir()).collect())
+        .unwrap_or_default()
+}
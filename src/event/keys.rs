@@ -0,0 +1,207 @@
+
+use super::window::{modifier, MODIFIERS};
+use crate::window::Window;
+use crate::utils::LogType;
+
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xproto::{ConnectionExt as _, GrabMode, KeyButMask, ModMask};
+use x11rb::protocol::xkb::{self, ConnectionExt as _};
+
+/// Keysym and modifiers which have to be held
+#[derive(Debug, Clone, Copy, Hash, PartialEq)]
+pub struct Key(u32, u16);
+
+/// Keys grabbed on the root window, so that they're reported whichever window has the focus
+#[derive(Default)]
+pub struct KeyGrabs {
+    keys: Vec<(Key, String)>,
+    // Keycode and modifiers of every grabbed key, `None` until keys are grabbed on the first refresh
+    grabbed: Option<Vec<(u8, u16, Key)>>,
+    held: Vec<(u8, Key)>
+}
+
+
+impl Key {
+    /// Parse modifiers and a key name joined with `+`, such as "super+b"
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let parts = s.split('+').map(|x| x.to_lowercase()).collect::<Vec<_>>();
+        let (key, mods) = match parts.split_last() {
+            Some((key, mods)) if !key.is_empty() => (key, mods),
+            _ => return Err("Expected a key, eg. super+b".to_string())
+        };
+
+        let mods = mods.iter()
+            .map(|x| modifier(x).ok_or_else(|| format!("Unknown modifier '{}'", x)))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter().fold(0, |a, b| a | b);
+
+        keysym(key).map(|x| Self(x, mods)).ok_or_else(|| format!("Unknown key '{}'", key))
+    }
+}
+
+impl KeyGrabs {
+    pub fn add(&mut self, key: Key, name: &str) {
+        if !self.keys.iter().any(|(k, _)| *k == key) {
+            self.keys.push((key, name.to_string()));
+        }
+    }
+
+    /// Grab keys that haven't been grabbed yet
+    pub fn grab(&mut self, window: &Window) {
+        if self.grabbed.is_some() || self.keys.is_empty() {
+            return;
+        }
+        let mut grabbed = vec!();
+
+        // Otherwise holding a key would report repeated releases and presses
+        let xkb = window.conn.extension_information(xkb::X11_EXTENSION_NAME).ok().flatten().is_some()
+            && window.conn.xkb_use_extension(1, 0).ok().and_then(|x| x.reply().ok()).is_some_and(|x| x.supported);
+        if xkb {
+            let flag = xkb::PerClientFlag::DetectableAutoRepeat as u32;
+            let _ = window.conn.xkb_per_client_flags(xkb::ID::UseCoreKbd as u16, flag, flag, 0u32, 0u32, 0u32)
+                .map(|x| x.reply());
+        }
+
+        let setup = window.conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let mapping = match window.conn.get_keyboard_mapping(min, max - min + 1).ok().and_then(|x| x.reply().ok()) {
+            Some(x) => x,
+            None => {
+                crate::log!(LogType::Error, "Failed to get the keyboard mapping, keys won't be grabbed");
+                self.grabbed = Some(grabbed);
+                return;
+            }
+        };
+        let per_keycode = (mapping.keysyms_per_keycode as usize).max(1);
+
+        // Caps Lock and Num Lock shouldn't prevent shortcuts from working
+        let lock = u16::from(ModMask::Lock);
+        let num_lock = u16::from(ModMask::M2);
+        let shift = u16::from(KeyButMask::Shift);
+
+        for (key, name) in self.keys.iter() {
+            // Keys typed with Shift, such as "plus" on most layouts, are grabbed with Shift held
+            let keycodes = mapping.keysyms.chunks(per_keycode).enumerate()
+                .filter_map(|(c, syms)| match syms.iter().take(2).position(|x| *x == key.0) {
+                    Some(0) => Some((min + c as u8, key.1)),
+                    Some(_) => Some((min + c as u8, key.1 | shift)),
+                    None => None
+                })
+                .collect::<Vec<_>>();
+
+            if keycodes.is_empty() {
+                crate::log!(LogType::Warning, "Key '{}' isn't on the keyboard", name);
+            }
+
+            'codes: for (code, mods) in keycodes {
+                let variants = [0, lock, num_lock, lock | num_lock].map(|x| mods | x);
+                for (i, variant) in variants.iter().enumerate() {
+                    let res = window.conn.grab_key(true, window.root, *variant, code, GrabMode::Async, GrabMode::Async)
+                        .map_err(|e| e.to_string())
+                        .and_then(|x| x.check().map_err(|e| format!("{:?}", e)));
+
+                    if let Err(e) = res {
+                        crate::log!(LogType::Warning, "Failed to grab key '{}', it may be used by another program: {}", name, e);
+                        for v in variants[..i].iter() {
+                            let _ = window.conn.ungrab_key(code, window.root, *v);
+                        }
+                        continue 'codes;
+                    }
+                }
+                grabbed.push((code, mods, *key));
+            }
+        }
+        self.grabbed = Some(grabbed);
+    }
+
+    /// Release all grabs and grab keys again, after the keyboard mapping has changed
+    pub fn regrab(&mut self, window: &Window) {
+        for (code, _, _) in self.grabbed.take().unwrap_or_default() {
+            let _ = window.conn.ungrab_key(code, window.root, ModMask::Any);
+        }
+        self.held.clear();
+        self.grab(window);
+    }
+
+    /// Keys pressed with the given keycode and modifiers, nothing when the key is repeated
+    pub fn press(&mut self, keycode: u8, state: u16) -> Vec<Key> {
+        if self.held.iter().any(|(c, _)| *c == keycode) {
+            return vec!();
+        }
+        let mods = state & MODIFIERS;
+
+        let pressed = self.grabbed.iter().flatten()
+            .filter(|(c, m, _)| *c == keycode && *m == mods)
+            .map(|(c, _, k)| (*c, *k))
+            .collect::<Vec<_>>();
+
+        self.held.extend(pressed.iter().cloned());
+        pressed.into_iter().map(|(_, k)| k).collect()
+    }
+
+    pub fn release(&mut self, keycode: u8) {
+        self.held.retain(|(c, _)| *c != keycode);
+    }
+
+    pub fn held(&self) -> impl Iterator<Item=&Key> {
+        self.held.iter().map(|(_, k)| k)
+    }
+}
+
+
+// Keysym of a key name, which is a character or one of a few names of special keys
+fn keysym(name: &str) -> Option<u32> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        // Keysyms of Latin-1 characters are equal to their codes, letters are mapped to lowercase ones
+        return Some(c as u32).filter(|x| (0x20..0x7f).contains(x) || (0xa0..0x100).contains(x));
+    }
+
+    if let Some(n) = name.strip_prefix('f').and_then(|x| x.parse::<u32>().ok()).filter(|x| (1..=35).contains(x)) {
+        return Some(0xffbe + n - 1);
+    }
+    if let Some(hex) = name.strip_prefix("0x") {
+        return u32::from_str_radix(hex, 16).ok();
+    }
+
+    Some(match name {
+        "space" => 0x20,
+        "plus" => 0x2b,
+        "backspace" => 0xff08,
+        "tab" => 0xff09,
+        "return" | "enter" => 0xff0d,
+        "pause" => 0xff13,
+        "escape" | "esc" => 0xff1b,
+        "home" => 0xff50,
+        "left" => 0xff51,
+        "up" => 0xff52,
+        "right" => 0xff53,
+        "down" => 0xff54,
+        "page_up" | "prior" => 0xff55,
+        "page_down" | "next" => 0xff56,
+        "end" => 0xff57,
+        "print" => 0xff61,
+        "insert" => 0xff63,
+        "menu" => 0xff67,
+        "delete" => 0xffff,
+        "shift" | "shift_l" => 0xffe1,
+        "shift_r" => 0xffe2,
+        "ctrl" | "control" | "control_l" => 0xffe3,
+        "control_r" => 0xffe4,
+        "alt" | "alt_l" => 0xffe9,
+        "alt_r" => 0xffea,
+        "super" | "super_l" => 0xffeb,
+        "super_r" => 0xffec,
+        "xf86monbrightnessup" => 0x1008ff02,
+        "xf86monbrightnessdown" => 0x1008ff03,
+        "xf86audiolowervolume" => 0x1008ff11,
+        "xf86audiomute" => 0x1008ff12,
+        "xf86audioraisevolume" => 0x1008ff13,
+        "xf86audioplay" => 0x1008ff14,
+        "xf86audiostop" => 0x1008ff15,
+        "xf86audioprev" => 0x1008ff16,
+        "xf86audionext" => 0x1008ff17,
+        "xf86audiomicmute" => 0x1008ffb2,
+        _ => return None
+    })
+}
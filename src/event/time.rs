@@ -0,0 +1,303 @@
+
+use super::{Event, EventTrait, EventListener};
+use crate::bar::Bar;
+use crate::command::CommandSharedState;
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+
+#[derive(Debug, Clone, Hash)]
+enum TimeEvent {
+    // Minutes since midnight, the end is exclusive and may be before the start to span midnight
+    Time(u32, u32),
+    // Bitmask of weekdays, Sunday is 0
+    Weekday(u8),
+    Cron(CronSchedule)
+}
+
+// Each field is a bitmask of allowed values
+#[derive(Debug, Clone, Hash)]
+struct CronSchedule {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    any_day: bool,
+    any_weekday: bool
+}
+
+// Local time, in the ranges used by cron
+struct LocalTime {
+    minute: u32,
+    hour: u32,
+    day: u32,
+    month: u32,
+    weekday: u32
+}
+
+pub struct TimeListener {
+    subscriptions: Vec<TimeEvent>
+}
+
+
+impl EventTrait for TimeEvent {
+    fn precedence(&self) -> u32 {300}
+    fn mouse_dependent(&self) -> bool {false}
+    fn is_expose(&self) -> bool {false}
+}
+
+crate::impl_hashed_simple!(TimeEvent, 100050);
+
+
+impl EventListener for TimeListener {
+
+    fn reported_events(&self) -> &'static[&'static str] {
+        const TIME_EVENTS: &'static[&'static str] = &[&"on_time", &"on_weekday", &"on_cron"];
+        TIME_EVENTS
+    }
+
+    fn event(&mut self, _cmd: &mut CommandSharedState, event: &String, settings: &String) -> Event {
+        let e = |msg: String| -> ! {panic!("Invalid event {}.{}: {}", event, settings, msg)};
+
+        let ev = match &event[..] {
+            "on_time" => {
+                let mut sett = settings.splitn(2, '-');
+                let start = sett.next().map(parse_time).unwrap_or_else(|| e("Expected a range like 08:00-18:00".to_string()));
+                let end   = sett.next().map(parse_time).unwrap_or_else(|| e("Expected a range like 08:00-18:00".to_string()));
+
+                TimeEvent::Time(start.unwrap_or_else(|x| e(x)), end.unwrap_or_else(|x| e(x)))
+            },
+            "on_weekday" => TimeEvent::Weekday(parse_field(settings, 0, 7, WEEKDAYS).unwrap_or_else(|x| e(x)) as u8),
+            "on_cron" => TimeEvent::Cron(CronSchedule::parse(settings).unwrap_or_else(|x| e(x))),
+            _ => panic!("Unknown event {}.{} (reported by TimeListener)", event, settings)
+        };
+
+        self.subscriptions.push(ev.clone());
+        Box::new(ev)
+    }
+
+    fn get(&mut self, _bar: &Bar, v: &mut Vec<Event>) {
+        if self.subscriptions.is_empty() {
+            return;
+        }
+        let t = LocalTime::now();
+
+        for i in self.subscriptions.iter().filter(|x| x.matches(&t)) {
+            v.push(Box::new(i.clone()));
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        if self.subscriptions.is_empty() {
+            return None;
+        }
+        // Every event changes on a minute boundary, time zones are offset by whole minutes
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let minute = Duration::from_secs(60);
+
+        Some(Instant::now() + minute - Duration::from_nanos((now.as_nanos() % minute.as_nanos()) as u64))
+    }
+}
+
+impl TimeListener {
+    pub fn new() -> Self {
+        Self {subscriptions: vec!()}
+    }
+}
+
+
+impl TimeEvent {
+    fn matches(&self, t: &LocalTime) -> bool {
+        match self {
+            Self::Time(start, end) => {
+                let now = t.hour * 60 + t.minute;
+                if start <= end {*start <= now && now < *end} else {now >= *start || now < *end}
+            },
+            Self::Weekday(days) => days & (1 << t.weekday) != 0,
+            Self::Cron(c) => c.matches(t)
+        }
+    }
+}
+
+impl CronSchedule {
+    /// Parse "minute hour day month weekday", each field may be `*`, a number, a range, a list and have a step
+    fn parse(s: &str) -> Result<Self, String> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err("Expected 5 fields: minute hour day month weekday".to_string());
+        }
+
+        Ok(Self {
+            minutes:  parse_field(fields[0], 0, 59, &[])?,
+            hours:    parse_field(fields[1], 0, 23, &[])? as u32,
+            days:     parse_field(fields[2], 1, 31, &[])? as u32,
+            months:   parse_field(fields[3], 1, 12, MONTHS)? as u16,
+            weekdays: parse_field(fields[4], 0, 7, WEEKDAYS)? as u8,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*"
+        })
+    }
+
+    fn matches(&self, t: &LocalTime) -> bool {
+        let day = self.days & (1 << t.day) != 0;
+        let weekday = self.weekdays & (1 << t.weekday) != 0;
+
+        // Like in cron, a day matches if either of the restricted day fields does
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday
+        };
+
+        self.minutes & (1 << t.minute) != 0
+            && self.hours & (1 << t.hour) != 0
+            && self.months & (1 << t.month) != 0
+            && day_matches
+    }
+}
+
+impl LocalTime {
+    fn now() -> Self {
+        let mut tm: libc::tm = unsafe {std::mem::zeroed()};
+        unsafe {
+            let t = libc::time(std::ptr::null_mut());
+            libc::localtime_r(&t, &mut tm);
+        }
+        Self {
+            minute: tm.tm_min as u32,
+            hour: tm.tm_hour as u32,
+            day: tm.tm_mday as u32,
+            month: tm.tm_mon as u32 + 1,
+            weekday: tm.tm_wday as u32
+        }
+    }
+}
+
+
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const MONTHS: &[&str] = &["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+// Parse HH:MM into minutes since midnight, 24:00 is allowed as the end of a day
+fn parse_time(s: &str) -> Result<u32, String> {
+    let mut parts = s.trim().splitn(2, ':');
+    let h = parts.next().and_then(|x| x.parse::<u32>().ok());
+    let m = parts.next().and_then(|x| x.parse::<u32>().ok());
+
+    match (h, m) {
+        (Some(h), Some(m)) if m < 60 && h * 60 + m <= 24 * 60 => Ok(h * 60 + m),
+        _ => Err(format!("'{}' is not a valid time, expected HH:MM", s))
+    }
+}
+
+// Parse a cron field into a bitmask, `names` are alternatives to numbers starting at `min`.
+// Weekday 7 is Sunday, just like 0
+fn parse_field(s: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |x: &str| -> Result<u32, String> {
+        let x = x.to_lowercase();
+        names.iter().position(|n| *n == x).map(|i| i as u32 + min)
+            .or_else(|| x.parse().ok())
+            .filter(|x| (min..=max).contains(x))
+            .ok_or_else(|| format!("'{}' is out of range {}-{}", x, min, max))
+    };
+
+    let mut mask = 0u64;
+    for part in s.split(',') {
+        let mut p = part.splitn(2, '/');
+        let range = p.next().unwrap_or_default();
+        let step = match p.next() {
+            Some(x) => x.parse::<u32>().ok().filter(|x| *x > 0).ok_or_else(|| format!("Invalid step in '{}'", part))?,
+            None => 1
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (value(a)?, value(b)?),
+                None => {let v = value(range)?; (v, if step > 1 {max} else {v})}
+            }
+        };
+        if start > end {
+            return Err(format!("Invalid range '{}'", range));
+        }
+
+        for i in (start..=end).step_by(step as usize) {
+            mask |= 1 << i;
+        }
+    }
+
+    // Sunday may be written as 7
+    if max == 7 && mask & (1 << 7) != 0 {
+        mask = (mask | 1) & !(1 << 7);
+    }
+    Ok(mask)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{CronSchedule, LocalTime, parse_field, parse_time, WEEKDAYS, MONTHS};
+
+    fn at(minute: u32, hour: u32, day: u32, month: u32, weekday: u32) -> LocalTime {
+        LocalTime {minute, hour, day, month, weekday}
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(parse_field("*", 0, 7, WEEKDAYS), Ok(0x7f));
+        assert_eq!(parse_field("5", 0, 59, &[]), Ok(1 << 5));
+        assert_eq!(parse_field("1-3,10", 0, 59, &[]), Ok(0b1110 | 1 << 10));
+        assert_eq!(parse_field("*/15", 0, 59, &[]), Ok(1 | 1 << 15 | 1 << 30 | 1 << 45));
+        assert_eq!(parse_field("10-20/5", 0, 59, &[]), Ok(1 << 10 | 1 << 15 | 1 << 20));
+        assert_eq!(parse_field("50/5", 0, 59, &[]), Ok(1 << 50 | 1 << 55));
+        assert_eq!(parse_field("mon-fri", 0, 7, WEEKDAYS), Ok(0b0111110));
+        assert_eq!(parse_field("Jan,dec", 1, 12, MONTHS), Ok(1 << 1 | 1 << 12));
+        assert_eq!(parse_field("7", 0, 7, WEEKDAYS), Ok(1));
+        assert_eq!(parse_field("5-7", 0, 7, WEEKDAYS), Ok(0b1100001));
+    }
+
+    #[test]
+    fn invalid_fields() {
+        assert!(parse_field("60", 0, 59, &[]).is_err());
+        assert!(parse_field("0", 1, 31, &[]).is_err());
+        assert!(parse_field("20-10", 0, 59, &[]).is_err());
+        assert!(parse_field("*/0", 0, 59, &[]).is_err());
+        assert!(parse_field("*/x", 0, 59, &[]).is_err());
+        assert!(parse_field("mon", 0, 59, &[]).is_err());
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("08:30"), Ok(8 * 60 + 30));
+        assert_eq!(parse_time(" 0:00 "), Ok(0));
+        assert_eq!(parse_time("24:00"), Ok(24 * 60));
+        assert!(parse_time("24:01").is_err());
+        assert!(parse_time("12:60").is_err());
+        assert!(parse_time("12").is_err());
+    }
+
+    #[test]
+    fn schedules() {
+        let c = CronSchedule::parse("*/30 9-17 * * mon-fri").unwrap();
+        assert!(c.matches(&at(30, 9, 1, 1, 1)));
+        assert!(!c.matches(&at(15, 9, 1, 1, 1)));
+        assert!(!c.matches(&at(0, 18, 1, 1, 1)));
+        assert!(!c.matches(&at(0, 9, 1, 1, 0)));
+
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("* * * 13 *").is_err());
+    }
+
+    #[test]
+    fn restricted_days_match_either() {
+        // The 1st of the month or any Monday
+        let c = CronSchedule::parse("0 12 1 * mon").unwrap();
+        assert!(c.matches(&at(0, 12, 1, 6, 3)));
+        assert!(c.matches(&at(0, 12, 8, 6, 1)));
+        assert!(!c.matches(&at(0, 12, 8, 6, 3)));
+
+        // With one of them unrestricted, both have to match
+        let c = CronSchedule::parse("0 12 1 * *").unwrap();
+        assert!(c.matches(&at(0, 12, 1, 6, 3)));
+        assert!(!c.matches(&at(0, 12, 8, 6, 1)));
+    }
+}
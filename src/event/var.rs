@@ -0,0 +1,64 @@
+
+use super::{Event, EventTrait, EventListener};
+use crate::bar::Bar;
+use crate::command::CommandSharedState;
+use crate::command::var::VarSingleton;
+
+
+#[derive(Debug, Clone, Hash)]
+struct VarIs(String, String);
+
+pub struct VarListener {
+    subscriptions: Vec<(String, String)>
+}
+
+
+impl EventTrait for VarIs {
+    fn precedence(&self) -> u32 {300}
+    fn mouse_dependent(&self) -> bool {false}
+    fn is_expose(&self) -> bool {false}
+}
+
+crate::impl_hashed_simple!(VarIs, 100040);
+
+
+impl EventListener for VarListener {
+
+    fn reported_events(&self) -> &'static[&'static str] {
+        const VAR_EVENTS: &'static[&'static str] = &[&"on_var"];
+        VAR_EVENTS
+    }
+
+    fn event(&mut self, _cmd: &mut CommandSharedState, event: &String, settings: &String) -> Event {
+        match &event[..] {
+            "on_var" => {
+
+                let mut sett = settings.splitn(2, '=');
+                let name  = sett.next().filter(|x| !x.is_empty()).expect(&format!("Failed to get variable name for event {}.{}", event, settings)).to_string();
+                let value = sett.next().expect(&format!("Failed to get value for event {}.{}", event, settings)).to_string();
+
+                self.subscriptions.push((name.clone(), value.clone()));
+
+                Box::new(VarIs(name, value))
+            },
+            _ => panic!("Unknown event {}.{} (reported by VarListener)", event, settings)
+        }
+    }
+
+    fn get(&mut self, bar: &Bar, v: &mut Vec<Event>) {
+        let mut cmdstate = bar.get_cmd_state();
+        let vars = cmdstate.get::<VarSingleton>(0);
+
+        for (n,val) in self.subscriptions.iter() {
+            if vars.get(n) == *val {
+                v.push(Box::new(VarIs(n.clone(), val.clone())));
+            }
+        }
+    }
+}
+
+impl VarListener {
+    pub fn new() -> Self {
+        Self {subscriptions: vec!()}
+    }
+}
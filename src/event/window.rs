@@ -1,24 +1,73 @@
 
 use super::{Event, EventTrait, EventListener};
+use super::keys::{Key, KeyGrabs};
 use crate::bar::Bar;
+use crate::window::Window;
 use crate::utils::LogType;
 
 use x11rb::protocol::Event as XEvent;
 use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{KeyButMask, Mapping};
+use x11rb::protocol::xinput::{self, ConnectionExt as _, DeviceClass, DeviceClassData, Fp3232, ScrollType};
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Default longest time between presses of a double click and time a button has to be held for a long press
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+const LONG_PRESS_TIME: Duration = Duration::from_millis(500);
+
+/// Modifiers taken into account, others such as Num Lock are ignored
+pub const MODIFIERS: u16 = KeyButMask::Shift as u16 | KeyButMask::Control as u16 | KeyButMask::Mod1 as u16 | KeyButMask::Mod4 as u16;
 
 #[derive(Debug, Clone, Hash)]
 enum WindowEvent {
     Expose,
     Hover,
-    ButtonPress(Option<u8>),
-    ButtonPressCont(Option<u8>),
-    ButtonRelease(Option<u8>),
-    ButtonReleaseCont(Option<u8>)
+    Enter,
+    Leave,
+    ButtonPress(Button),
+    ButtonPressCont(Button),
+    ButtonRelease(Button),
+    ButtonReleaseCont(Button),
+    // With the longest time between presses or the time a button has to be held, in milliseconds
+    DoubleClick(Button, u32),
+    LongPress(Button, u32),
+    // Moving the pointer while a button (any if `None`) is held after pressing it over the widget
+    Drag(Option<u8>),
+    Scroll,
+    KeyPress(Key),
+    KeyPressCont(Key)
 }
 
+// Mouse button (any if `None`) and modifiers which have to be held (any if 0)
+#[derive(Debug, Clone, Copy, Hash, PartialEq)]
+struct Button(Option<u8>, u16);
+
 pub struct WindowListener {
     button_state: [bool; 32],
-    num_buttons_pressed: usize
+    num_buttons_pressed: usize,
+    // When each button was pressed and the modifiers held at the time
+    press_time: [Option<(Instant, u16)>; 32],
+    last_press: Option<(u8, Instant)>,
+    double_clicks: Vec<(Button, Duration)>,
+    // Along with the press each one was reported for
+    long_presses: Vec<(Button, Duration, Option<Instant>)>,
+    // Scroll valuators of master pointers by device and valuator number: whether they're vertical and the size of a scroll step.
+    // Queried on the first refresh, `None` until then
+    scroll_valuators: Option<HashMap<(u16, u16), (bool, f64)>>,
+    // Last value of each scroll valuator, deltas are counted from it
+    scroll_values: HashMap<(u16, u16), f64>,
+    scrolled: (f64, f64),
+    keys: KeyGrabs
+}
+
+/// Hover state of a bar or a widget, which follows the pointer after `hover_delay` or `leave_delay` seconds
+#[derive(Default)]
+pub struct HoverState {
+    hovered: bool,
+    // Since when the pointer has been on the other side, and how long it has to stay there
+    pending: Option<(Instant, f32)>
 }
 
 
@@ -27,22 +76,36 @@ crate::impl_hashed_simple!(WindowEvent, 100020);
 impl EventTrait for WindowEvent {
     fn precedence(&self) -> u32 {
         match self {
-            Self::ButtonPress(b) => 101 + add_precedence(b),
-            Self::ButtonRelease(b) => 101 + add_precedence(b),
-            Self::ButtonPressCont(b) => 102 + add_precedence(b),
-            Self::ButtonReleaseCont(b) => 102 + add_precedence(b),
+            Self::DoubleClick(b, _) => 99 + add_precedence(b),
+            Self::LongPress(b, _) => 99 + add_precedence(b),
+            Self::Drag(b) => 99 + add_precedence(&Button(*b, 0)),
+            Self::Scroll => 100,
+            Self::ButtonPress(b) => 100 + add_precedence(b),
+            Self::ButtonRelease(b) => 100 + add_precedence(b),
+            Self::ButtonPressCont(b) => 101 + add_precedence(b),
+            Self::ButtonReleaseCont(b) => 101 + add_precedence(b),
+            Self::KeyPress(_) => 110,
+            Self::KeyPressCont(_) => 111,
             Self::Expose => 160,
+            Self::Enter => 190,
+            Self::Leave => 190,
             Self::Hover => 200,
         }
     }
 
+    // Hover and drag events are only reported to the bar or widget they concern, see `HoverState` and `drag_events`
     fn mouse_dependent(&self) -> bool {
         match self {
-            Self::Expose => false,
+            Self::Expose | Self::Hover | Self::Enter | Self::Leave | Self::Drag(_) => false,
+            Self::KeyPress(_) | Self::KeyPressCont(_) => false,
             _ => true
         }
     }
 
+    fn is_hover(&self) -> bool {
+        matches!(self, Self::Hover | Self::Enter | Self::Leave)
+    }
+
     fn is_expose(&self) -> bool {
         match self {
             Self::Expose => true,
@@ -56,21 +119,64 @@ impl EventListener for WindowListener {
     fn reported_events(&self) -> &'static[&'static str] {
         const WINDOW_EVENTS: &'static[&'static str] = &[
             "on_hover",
+            "on_enter",
+            "on_leave",
             "on_press",
             "on_press_cont",
             "on_release",
-            "on_release_cont"
+            "on_release_cont",
+            "on_double_click",
+            "on_long_press",
+            "on_drag",
+            "on_scroll",
+            "on_key",
+            "on_key_cont"
         ];
         WINDOW_EVENTS
     }
 
     fn event(&mut self, _cmd: &mut crate::command::CommandSharedState, event: &String, settings: &String) -> Event {
+        if event.starts_with("on_key") {
+            let key = Key::parse(settings).unwrap_or_else(|e| panic!("Invalid event {}.{}: {}", event, settings, e));
+            self.keys.add(key, settings);
+
+            return Box::new(if event == "on_key" {WindowEvent::KeyPress(key)} else {WindowEvent::KeyPressCont(key)});
+        }
+
+        let mut sett = settings.splitn(2, '=');
+        let button = mouse_button(sett.next().unwrap_or_default());
+        let time = sett.next().map(|x| x.parse::<f32>().ok()
+            .and_then(|x| Duration::try_from_secs_f32(x).ok())
+            .unwrap_or_else(|| panic!("Invalid time in event {}.{}", event, settings)));
+
+        let gesture = matches!(&event[..], "on_double_click" | "on_long_press");
+        if time.is_some() && !gesture {
+            panic!("Event {} doesn't take a time ({}.{})", event, event, settings);
+        }
+        if button.1 != 0 && (event.ends_with("_cont") || event == "on_drag") {
+            panic!("Event {} doesn't support modifiers ({}.{})", event, event, settings);
+        }
+
         Box::new(match &event[..] {
             "on_hover" => WindowEvent::Hover,
-            "on_press" => WindowEvent::ButtonPress(mouse_button(settings.clone())),
-            "on_press_cont" => WindowEvent::ButtonPressCont(mouse_button(settings.clone())),
-            "on_release" => WindowEvent::ButtonRelease(mouse_button(settings.clone())),
-            "on_release_cont" => WindowEvent::ButtonReleaseCont(mouse_button(settings.clone())),
+            "on_enter" => WindowEvent::Enter,
+            "on_leave" => WindowEvent::Leave,
+            "on_press" => WindowEvent::ButtonPress(button),
+            "on_press_cont" => WindowEvent::ButtonPressCont(button),
+            "on_release" => WindowEvent::ButtonRelease(button),
+            "on_release_cont" => WindowEvent::ButtonReleaseCont(button),
+            "on_double_click" => {
+                let time = time.unwrap_or(DOUBLE_CLICK_TIME);
+                self.double_clicks.push((button, time));
+                WindowEvent::DoubleClick(button, time.as_millis() as u32)
+            },
+            "on_long_press" => {
+                let time = time.unwrap_or(LONG_PRESS_TIME);
+                self.long_presses.push((button, time, None));
+                WindowEvent::LongPress(button, time.as_millis() as u32)
+            },
+            "on_drag" => WindowEvent::Drag(button.0),
+            "on_scroll" if settings.is_empty() => WindowEvent::Scroll,
             _ => panic!("Unknown event {}.{} (reported by WindowListener)", event, settings.clone())
         })
     }
@@ -79,50 +185,117 @@ impl EventListener for WindowListener {
     fn get(&mut self, bar: &Bar, v: &mut Vec<Event>) {
         const E: &str = "Failed to poll X events";
         
-        let conn = &bar.get_window().conn;
-        let ev_opt = conn.poll_for_event().expect(E);
+        let window = bar.get_window();
+        if window.xinput && self.scroll_valuators.is_none() {
+            self.scroll_valuators = Some(query_scroll_valuators(window));
+        }
+        self.scrolled = (0.0, 0.0);
+        self.keys.grab(window);
+
+        let ev_opt = window.conn.poll_for_event().expect(E);
         
         if let Some(e1) = ev_opt {
-            self.xevents_to_events(e1, v);
+            self.xevents_to_events(e1, window, v);
 
-            while let Some(e2) = conn.poll_for_event().expect(E) {
-                self.xevents_to_events(e2, v);
+            while let Some(e2) = window.conn.poll_for_event().expect(E) {
+                self.xevents_to_events(e2, window, v);
             }
         }
 
         for i in 0u8..32u8 {
             v.push( Box::new( if self.button_state[i as usize] {
-                WindowEvent::ButtonPressCont(Some(i))
+                WindowEvent::ButtonPressCont(Button(Some(i), 0))
             } else {
-                WindowEvent::ButtonReleaseCont(Some(i))
+                WindowEvent::ButtonReleaseCont(Button(Some(i), 0))
             }));
         }
 
         v.push( Box::new( if self.num_buttons_pressed > 0 {
-            WindowEvent::ButtonPressCont(None)
+            WindowEvent::ButtonPressCont(Button(None, 0))
         } else {
-            WindowEvent::ButtonReleaseCont(None)
+            WindowEvent::ButtonReleaseCont(Button(None, 0))
         }));
-        
 
-        v.push(Box::new(WindowEvent::Hover))
+        for key in self.keys.held() {
+            v.push(Box::new(WindowEvent::KeyPressCont(*key)));
+        }
+
+        window.set_scroll(self.scrolled);
+        if self.scrolled != (0.0, 0.0) {
+            v.push(Box::new(WindowEvent::Scroll));
+        }
+
+        // Long presses are reported once for each press, as soon as the button has been held long enough
+        let now = Instant::now();
+        for c in 0..self.long_presses.len() {
+            let (button, time, reported) = self.long_presses[c];
+
+            if let Some(pressed) = self.held_since(button) {
+                if reported != Some(pressed) && now >= pressed + time {
+                    self.long_presses[c].2 = Some(pressed);
+                    v.push(Box::new(WindowEvent::LongPress(button, time.as_millis() as u32)));
+                }
+            }
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.long_presses.iter()
+            .filter_map(|(button, time, reported)| self.held_since(*button)
+                .filter(|x| Some(*x) != *reported)
+                .map(|x| x + *time))
+            .min()
     }
 }
 
 impl WindowListener {
     pub fn new() -> Self {
-        Self {button_state: [false; 32], num_buttons_pressed: 0}
+        Self {button_state: [false; 32], num_buttons_pressed: 0, press_time: [None; 32], last_press: None,
+              double_clicks: vec!(), long_presses: vec!(),
+              scroll_valuators: None, scroll_values: HashMap::new(), scrolled: (0.0, 0.0),
+              keys: KeyGrabs::default()}
+    }
+
+    // When the earliest of currently held buttons matching `button` was pressed
+    fn held_since(&self, button: Button) -> Option<Instant> {
+        self.press_time.iter().enumerate()
+            .filter_map(|(b, x)| x.filter(|(_, mods)| button.matches(b as u8, *mods)))
+            .map(|(time, _)| time)
+            .min()
     }
 
-    fn xevents_to_events(&mut self, ev: XEvent, v: &mut Vec<Event>) {
+    fn xevents_to_events(&mut self, ev: XEvent, window: &Window, v: &mut Vec<Event>) {
         let mut fallback = false;
         let warn_too_large_id = |x| {crate::log!(LogType::Warning, "Mouse button with ID above 31 pressed ({}), not registering continuous events", x);};
 
         match ev {
             XEvent::Expose(_) => v.push(Box::new(WindowEvent::Expose)),
             XEvent::ButtonPress(x) => {
-                v.push(Box::new(WindowEvent::ButtonPress(None)));
-                v.push(Box::new(WindowEvent::ButtonPress(Some(x.detail))));
+                window.set_pointer(Some((x.root_x, x.root_y)));
+                let mods = x.state & MODIFIERS;
+                push_buttons(v, WindowEvent::ButtonPress, x.detail, mods);
+
+                // The press after a double click doesn't start another one
+                let now = Instant::now();
+                let mut double = false;
+                if let Some((b, t)) = self.last_press.filter(|(b, _)| *b == x.detail) {
+                    for (button, time) in self.double_clicks.iter().filter(|(button, _)| button.matches(b, mods)) {
+                        if now.duration_since(t) <= *time {
+                            double = true;
+                            v.push(Box::new(WindowEvent::DoubleClick(*button, time.as_millis() as u32)));
+                        }
+                    }
+                }
+                self.last_press = if double {None} else {Some((x.detail, now))};
+
+                if let Some(p) = self.press_time.get_mut(x.detail as usize) {
+                    *p = Some((now, mods));
+                }
+
+                // Scroll wheel "buttons" are released right away, so they don't start dragging
+                if window.get_press().is_none() && !(4..=7).contains(&x.detail) {
+                    window.set_press(Some((x.detail, x.root_x, x.root_y)));
+                }
 
                 let state = self.button_state.get_mut(x.detail as usize).unwrap_or_else(|| {warn_too_large_id(x.detail); &mut fallback});
                 if *state == false {
@@ -131,8 +304,15 @@ impl WindowListener {
                 }
             },
             XEvent::ButtonRelease(x) => {
-                v.push(Box::new(WindowEvent::ButtonRelease(None))); 
-                v.push(Box::new(WindowEvent::ButtonRelease(Some(x.detail))));
+                window.set_pointer(Some((x.root_x, x.root_y)));
+                push_buttons(v, WindowEvent::ButtonRelease, x.detail, x.state & MODIFIERS);
+
+                if let Some(p) = self.press_time.get_mut(x.detail as usize) {
+                    *p = None;
+                }
+                if window.get_press().is_some_and(|(b, _, _)| b == x.detail) {
+                    window.set_press(None);
+                }
 
                 let state = self.button_state.get_mut(x.detail as usize).unwrap_or_else(|| {warn_too_large_id(x.detail); &mut fallback});
                 if *state == true {
@@ -140,29 +320,184 @@ impl WindowListener {
                     self.num_buttons_pressed -= 1;
                 }
             },
+            XEvent::MotionNotify(x) => window.set_pointer(Some((x.root_x, x.root_y))),
+            XEvent::EnterNotify(x) => {
+                window.set_pointer(Some((x.root_x, x.root_y)));
+                // Scroll valuators may have changed elsewhere
+                self.scroll_values.clear();
+            },
+            // The pointer may have moved from the bar to the trigger window, which reports entering afterwards
+            XEvent::LeaveNotify(_) => window.set_pointer(None),
+            // Grabbed keys, reported wherever the focus is
+            XEvent::KeyPress(x) => {
+                for key in self.keys.press(x.detail, x.state) {
+                    v.push(Box::new(WindowEvent::KeyPress(key)));
+                }
+            },
+            XEvent::KeyRelease(x) => self.keys.release(x.detail),
+            XEvent::MappingNotify(x) => {
+                if x.request == Mapping::Keyboard {
+                    self.keys.regrab(window);
+                }
+            },
+            XEvent::XinputMotion(x) => {
+                window.set_pointer(Some(((x.root_x >> 16) as i16, (x.root_y >> 16) as i16)));
+                self.add_scroll(x.deviceid, &x.valuator_mask, &x.axisvalues);
+            },
+            // Master pointers take over the classes of the device used last
+            XEvent::XinputDeviceChanged(x) => {
+                if let Some(valuators) = self.scroll_valuators.as_mut() {
+                    valuators.retain(|(device, _), _| *device != x.deviceid);
+                    valuators.extend(scroll_classes(&x.classes).map(|(n, vertical, step)| ((x.deviceid, n), (vertical, step))));
+                }
+                self.scroll_values.retain(|(device, _), _| *device != x.deviceid);
+            },
             _ => { crate::log!(LogType::Warning, "Unknown X event {:?}", ev); }
         }
     }
+
+    // Accumulate changes of scroll valuators, given by a mask of valuators and values of the set ones
+    fn add_scroll(&mut self, device: u16, mask: &[u32], values: &[Fp3232]) {
+        let valuators = match self.scroll_valuators.as_ref() {
+            Some(x) => x,
+            None => return
+        };
+
+        let numbers = (0..mask.len() as u16 * 32).filter(|n| mask[*n as usize / 32] & (1 << (n % 32)) != 0);
+        for (n, value) in numbers.zip(values) {
+            let (vertical, step) = match valuators.get(&(device, n)) {
+                Some(x) => *x,
+                None => continue
+            };
+            let value = value.integral as f64 + value.frac as f64 / (1u64 << 32) as f64;
+
+            if let Some(last) = self.scroll_values.insert((device, n), value) {
+                let delta = (value - last) / step;
+                if vertical {self.scrolled.1 += delta} else {self.scrolled.0 += delta}
+            }
+        }
+    }
 }
 
 
 
-fn mouse_button(s: String) -> Option<u8> {
-    match &s[..] {
-        "" => None,
-        "left" => Some(1), 
-        "middle" => Some(2), 
-        "right" => Some(3), 
-        "scroll_up" => Some(4), 
-        "scroll_down" => Some(5), 
-        _ => Some(u8::from_str_radix(&s, 10)
-                  .expect("Mouse button must be either a number or one of: (left, middle, right, scroll_up, scroll_down)"))
+impl HoverState {
+    /// Update with whether the pointer is inside and add hover events to `v`
+    pub fn update(&mut self, inside: bool, hover_delay: f32, leave_delay: f32, v: &mut Vec<Event>) {
+        if inside == self.hovered {
+            self.pending = None;
+        }
+        else {
+            let delay = if inside {hover_delay} else {leave_delay};
+            let (since, _) = *self.pending.get_or_insert((Instant::now(), delay));
+
+            if since.elapsed().as_secs_f32() >= delay {
+                self.hovered = inside;
+                self.pending = None;
+                v.push(Box::new(if inside {WindowEvent::Enter} else {WindowEvent::Leave}));
+            }
+        }
+
+        if self.hovered {
+            v.push(Box::new(WindowEvent::Hover));
+        }
+    }
+
+    /// When the pending change happens if the pointer stays where it is
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.and_then(|(since, delay)| Duration::try_from_secs_f32(delay).ok().and_then(|x| since.checked_add(x)))
     }
 }
 
-fn add_precedence(b: &Option<u8>) -> u32 {
-    match b {
+
+impl Button {
+    fn matches(&self, button: u8, mods: u16) -> bool {
+        self.0.is_none_or(|x| x == button) && (self.1 == 0 || self.1 == mods)
+    }
+}
+
+
+// Events for any button and the given one, then the same with modifiers if any are held
+fn push_buttons(v: &mut Vec<Event>, event: fn(Button) -> WindowEvent, button: u8, mods: u16) {
+    v.push(Box::new(event(Button(None, 0))));
+    v.push(Box::new(event(Button(Some(button), 0))));
+
+    if mods != 0 {
+        v.push(Box::new(event(Button(None, mods))));
+        v.push(Box::new(event(Button(Some(button), mods))));
+    }
+}
+
+// Parse a button optionally combined with modifiers, such as "left+ctrl+shift"
+fn mouse_button(s: &str) -> Button {
+    let mut button = None;
+    let mut mods = 0;
+
+    for part in s.split('+').filter(|x| !x.is_empty()) {
+        if let Some(m) = modifier(part) {
+            mods |= m;
+            continue;
+        }
+        if button.is_some() {
+            panic!("More than one mouse button given in '{}'", s);
+        }
+        button = Some(match part {
+            "left" => 1,
+            "middle" => 2,
+            "right" => 3,
+            "scroll_up" => 4,
+            "scroll_down" => 5,
+            _ => u8::from_str_radix(part, 10)
+                  .expect("Mouse button must be either a number or one of: (left, middle, right, scroll_up, scroll_down)")
+        });
+    }
+    Button(button, mods)
+}
+
+/// Add drag events of a widget the given button was pressed over
+pub fn drag_events(button: u8, v: &mut Vec<Event>) {
+    v.push(Box::new(WindowEvent::Drag(None)));
+    v.push(Box::new(WindowEvent::Drag(Some(button))));
+}
+
+// Scroll valuators of every master pointer
+fn query_scroll_valuators(window: &Window) -> HashMap<(u16, u16), (bool, f64)> {
+    let reply = window.conn.xinput_xi_query_device(xinput::Device::AllMaster).ok().and_then(|x| x.reply().ok());
+    if reply.is_none() {
+        crate::log!(LogType::Warning, "Failed to query input devices, smooth scrolling won't be reported");
+    }
+
+    reply.map(|x| x.infos).unwrap_or_default().iter()
+        .flat_map(|d| scroll_classes(&d.classes).map(move |(n, vertical, step)| ((d.deviceid, n), (vertical, step))))
+        .collect()
+}
+
+// Valuator number, whether it's vertical and the size of a scroll step of every scroll class
+fn scroll_classes(classes: &[DeviceClass]) -> impl Iterator<Item=(u16, bool, f64)> + '_ {
+    classes.iter().filter_map(|c| match &c.data {
+        DeviceClassData::Scroll(s) => {
+            let step = s.increment.integral as f64 + s.increment.frac as f64 / (1u64 << 32) as f64;
+            Some((s.number, s.scroll_type == ScrollType::Vertical, if step == 0.0 {1.0} else {step}))
+        },
+        _ => None
+    })
+}
+
+/// X modifier mask of a modifier name
+pub fn modifier(name: &str) -> Option<u16> {
+    match name {
+        "shift" => Some(KeyButMask::Shift.into()),
+        "ctrl" | "control" => Some(KeyButMask::Control.into()),
+        "alt" | "mod1" => Some(KeyButMask::Mod1.into()),
+        "super" | "mod4" => Some(KeyButMask::Mod4.into()),
+        _ => None
+    }
+}
+
+fn add_precedence(b: &Button) -> u32 {
+    let button = match b.0 {
         Some(_) => 0,
         None => 5
-    }
+    };
+    button + (b.1 == 0) as u32
 }
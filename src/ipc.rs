@@ -0,0 +1,79 @@
+use crate::utils::LogType;
+
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::PathBuf;
+
+/// A named pipe through which other programs can send commands to the bar, one per line
+pub struct Ipc {
+    file: Option<File>,
+    pending: Vec<u8>
+}
+
+
+impl Ipc {
+    pub fn new(name: &str) -> Self {
+        let path = path(name);
+
+        let exists = std::fs::metadata(&path).map(|x| x.file_type().is_fifo()).unwrap_or(false);
+        if !exists {
+            let _ = std::fs::remove_file(&path);
+            let c_path = CString::new(path.as_os_str().as_bytes()).expect("Invalid IPC pipe path");
+
+            if unsafe {libc::mkfifo(c_path.as_ptr(), 0o600)} != 0 {
+                crate::log!(LogType::Warning, "Failed to create IPC pipe {}: {}", path.display(), std::io::Error::last_os_error());
+                return Self {file: None, pending: vec!()};
+            }
+        }
+
+        // Opened for writing as well, so that the pipe doesn't report EOF when a writer closes it
+        let file = OpenOptions::new().read(true).write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)
+            .map_err(|e| {crate::log!(LogType::Warning, "Failed to open IPC pipe {}: {}", path.display(), e);})
+            .ok();
+
+        Self {file, pending: vec!()}
+    }
+
+    pub fn fd(&self) -> Option<RawFd> {
+        self.file.as_ref().map(|x| x.as_raw_fd())
+    }
+
+    /// Complete lines received since the last call
+    pub fn read(&mut self) -> Vec<String> {
+        let file = match self.file.as_mut() {
+            Some(x) => x,
+            None => return vec!()
+        };
+
+        let mut buf = [0u8; 4096];
+        while let Ok(n) = file.read(&mut buf) {
+            if n == 0 {break}
+            self.pending.extend_from_slice(&buf[..n]);
+        }
+
+        let end = match self.pending.iter().rposition(|x| *x == b'\n') {
+            Some(x) => x,
+            None => return vec!()
+        };
+        let complete = self.pending.drain(..=end).collect::<Vec<_>>();
+
+        String::from_utf8_lossy(&complete).lines()
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .collect()
+    }
+}
+
+
+/// Location of the pipe of the bar with the given config name
+pub fn path(name: &str) -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("ravenbar-{}", name))
+}
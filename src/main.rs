@@ -8,8 +8,17 @@ mod properties;
 mod event;
 mod draw;
 mod utils;
+mod ipc;
+mod mainloop;
 
 use config::config_dir;
+use command::wakeup;
+use utils::LogType;
+
+use std::time::{Duration, Instant};
+
+// Refreshes are at least this far apart, even when events keep coming
+const FRAME_TIME: Duration = Duration::from_millis(16);
 
 use structopt::StructOpt;
 
@@ -39,7 +48,7 @@ fn main() {
         }
     }.expect("Failed to check/create config directory");
 
-    let file = std::path::PathBuf::from(config_dir()).join(opt.config + ".yml");
+    let file = std::path::PathBuf::from(config_dir()).join(opt.config.clone() + ".yml");
     
     if opt.example_config {
         config::write_default_config(file).expect("Failed to write config");
@@ -48,12 +57,31 @@ fn main() {
         let config = config::BarConfig::new(file).expect("Failed to parse config");
 
         let mut b = bar::Bar::create(config);
+        let mut ipc = ipc::Ipc::new(&opt.config);
+        let mut main_loop = mainloop::MainLoop::new();
 
         loop {
-            b.refresh(false);
-            std::thread::sleep(std::time::Duration::from_millis(16));
-
+            let frame = Instant::now();
             b.flush();
+
+            let (mut fds, deadline) = b.wakeup();
+            fds.push(wakeup::notify_fd());
+            fds.extend(ipc.fd());
+            main_loop.wait(&fds, deadline.map(|x| x.max(frame + FRAME_TIME)));
+
+            if frame.elapsed() < FRAME_TIME {
+                std::thread::sleep(FRAME_TIME - frame.elapsed());
+            }
+            wakeup::clear();
+
+            let mut force = false;
+            for msg in ipc.read() {
+                match &msg[..] {
+                    "refresh" => force = true,
+                    _ => {crate::log!(LogType::Warning, "Unknown IPC command '{}'", msg);}
+                }
+            }
+            b.refresh(force);
         }
     }
 }
@@ -0,0 +1,53 @@
+use std::collections::HashSet;
+use std::os::unix::io::RawFd;
+use std::time::Instant;
+
+/// Waits until one of the watched descriptors becomes readable or a deadline passes
+pub struct MainLoop {
+    epoll: RawFd,
+    registered: HashSet<RawFd>
+}
+
+
+impl MainLoop {
+    pub fn new() -> Self {
+        let epoll = unsafe {libc::epoll_create1(libc::EPOLL_CLOEXEC)};
+        if epoll < 0 {
+            panic!("Failed to create epoll instance: {}", std::io::Error::last_os_error());
+        }
+        Self {epoll, registered: HashSet::new()}
+    }
+
+    /// Block until any of `fds` is readable or until `deadline`, forever if there's none
+    pub fn wait(&mut self, fds: &[RawFd], deadline: Option<Instant>) {
+        let fds = fds.iter().cloned().collect::<HashSet<_>>();
+
+        for fd in self.registered.difference(&fds) {
+            unsafe {libc::epoll_ctl(self.epoll, libc::EPOLL_CTL_DEL, *fd, std::ptr::null_mut());}
+        }
+        for fd in fds.difference(&self.registered) {
+            let mut event = libc::epoll_event {events: libc::EPOLLIN as u32, u64: *fd as u64};
+            unsafe {libc::epoll_ctl(self.epoll, libc::EPOLL_CTL_ADD, *fd, &mut event);}
+        }
+        self.registered = fds;
+
+        // Rounded up, so that the loop doesn't wake up just before the deadline
+        let timeout = match deadline {
+            Some(t) => {
+                let d = t.saturating_duration_since(Instant::now());
+                (d.as_micros() as i64 + 999) / 1000
+            }
+            None => -1
+        };
+
+        let mut events = [libc::epoll_event {events: 0, u64: 0}; 16];
+        let res = unsafe {libc::epoll_wait(self.epoll, events.as_mut_ptr(), events.len() as i32, timeout.min(i32::MAX as i64) as i32)};
+
+        if res < 0 {
+            let e = std::io::Error::last_os_error();
+            if e.kind() != std::io::ErrorKind::Interrupted {
+                panic!("Failed to wait for events: {}", e);
+            }
+        }
+    }
+}
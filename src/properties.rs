@@ -32,6 +32,11 @@ impl<T> Property<T> {
         }
         panic!("Bar/Widget property error (this error should be impossible)");
     }
+
+    /// Whether the value may change when the pointer moves
+    pub fn mouse_dependent(&self) -> bool {
+        self.map.keys().any(|x| x.mouse_dependent() || x.is_hover())
+    }
 }
 
 macro_rules! property {
@@ -138,7 +143,11 @@ property_struct!(WidgetProperties, WidgetPropertiesCurrent, BarConfigWidgetPrope
     command:        Command from Value = Command::from(Value::String("".to_string())),
     action:         Command from Value = Command::from(Value::String("".to_string())),
     border_factor:  f32 from f32 = 0.75,
-    interval:       f32 from f32 = 5.0
+    interval:       f32 from f32 = 5.0,
+    hover_delay:    f32 from f32 = 0.0,
+    leave_delay:    f32 from f32 = 0.0,
+    id:             String from String = String::new(),
+    source:         String from String = String::new()
 );
 
 // Bar properties
@@ -152,6 +161,8 @@ property_struct!(BarProperties, BarPropertiesCurrent, BarConfigProperties,
     solid:          bool from bool = true,
     above:          bool from bool = false,
     below:          bool from bool = false,
-    visible:        bool from bool = true
+    visible:        bool from bool = true,
+    hover_delay:    f32 from f32 = 0.0,
+    leave_delay:    f32 from f32 = 0.0
 );
 
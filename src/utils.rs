@@ -88,6 +88,18 @@ pub fn human_readable_p10(n: u64) -> String {
     format!("{:.2}{}", n as f64 / div as f64, suffix)
 }
 
+pub fn human_readable_f64(n: f64) -> String {
+    let (div, suffix) : (f64, &str) = 
+        if      n.abs() > 1e15 {(1e15, "P")}
+        else if n.abs() > 1e12 {(1e12, "T")}
+        else if n.abs() > 1e9  {(1e9,  "G")}
+        else if n.abs() > 1e6  {(1e6,  "M")}
+        else if n.abs() > 1e3  {(1e3,  "K")}
+        else {(1.0, "")};
+
+    format!("{:.2}{}", n / div, suffix)
+}
+
 pub fn find_human_readable(string: impl Iterator<Item = char>) -> Option<f64> {
     
     let mut dotfound = false;
@@ -97,7 +109,17 @@ pub fn find_human_readable(string: impl Iterator<Item = char>) -> Option<f64> {
 
     let mut valstring = String::with_capacity(20);
 
-    for i in string.skip_while(|x| !x.is_numeric()) {
+    // A minus sign right before the number makes it negative, eg. "-5°C", unless it's part of a word like "core-1"
+    let mut negative = false;
+    let mut prev: Option<char> = None;
+    let mut string = string.peekable();
+    while let Some(c) = string.next_if(|x| !x.is_numeric()) {
+        negative = c == '-' && prev.is_none_or(char::is_whitespace);
+        prev = Some(c);
+    }
+    let sign = if negative {-1.0} else {1.0};
+
+    for i in string {
         if numberfound {
             if i == 'i' {
                 pow10 = true;
@@ -130,10 +152,42 @@ pub fn find_human_readable(string: impl Iterator<Item = char>) -> Option<f64> {
         }
     }
 
+    // A number may also end the string, e.g. "5" without a newline
+    let numberfound = numberfound || !valstring.is_empty();
+
     match (numberfound, str::parse::<f64>(&valstring[..]), pow10) {
         (false, _, _)        => None,
         (true, Err(_), _)    => None,
-        (true, Ok(x), true)  => Some(x * 10.0f64.powi(3*magnitude)),
-        (true, Ok(x), false) => Some(x * 2.0f64.powi(10*magnitude)),
+        (true, Ok(x), true)  => Some(sign * x * 10.0f64.powi(3*magnitude)),
+        (true, Ok(x), false) => Some(sign * x * 2.0f64.powi(10*magnitude)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::find_human_readable;
+
+    fn find(s: &str) -> Option<f64> {
+        find_human_readable(s.chars())
+    }
+
+    #[test]
+    fn finds_numbers() {
+        assert_eq!(find("42"), Some(42.0));
+        assert_eq!(find("1.5k"), Some(1500.0));
+        assert_eq!(find("load: 0.75\n"), Some(0.75));
+        assert_eq!(find("none"), None);
+    }
+
+    #[test]
+    fn finds_negative_numbers() {
+        assert_eq!(find("-5"), Some(-5.0));
+        assert_eq!(find("-5°C"), Some(-5.0));
+        assert_eq!(find("10-20"), Some(10.0));
+        assert_eq!(find("a - 3"), Some(3.0));
+        assert_eq!(find("temp: -5"), Some(-5.0));
+        assert_eq!(find("core-1 45°C"), Some(1.0));
+        assert_eq!(find("eth-0"), Some(0.0));
     }
 }
@@ -1,9 +1,12 @@
 
 use std::error::Error;
+use std::cell::Cell;
+use std::os::unix::io::{AsRawFd, RawFd};
 
 use x11rb::protocol::xproto::*;
 use x11rb::protocol::xproto::{ConnectionExt as _};
 use x11rb::protocol::render::{ConnectionExt as _};
+use x11rb::protocol::xinput::{self, ConnectionExt as _};
 use x11rb::connection::{Connection, RequestConnection};
 use x11rb::wrapper::ConnectionExt;
 use x11rb::atom_manager;
@@ -42,6 +45,17 @@ pub struct Window {
     pub fc: Fontconfig,
     pub ft: Library,
 
+    // Input-only window receiving pointer events where the bar would be if it wasn't hovered
+    trigger: u32,
+    // Pointer position, known only while it's over the bar or the trigger window
+    pointer: Cell<Option<(i16, i16)>>,
+    // Button being held and where it was pressed
+    press: Cell<Option<(u8, i16, i16)>>,
+    // Smooth scrolling reported since the last refresh, in scroll steps
+    scroll: Cell<(f64, f64)>,
+    // XInput 2.1 is available, so motion events also carry smooth scrolling
+    pub xinput: bool,
+
     screen: Screen,
     atoms: Atoms
 }
@@ -246,9 +260,38 @@ impl Window {
                                 .colormap(colormap)
                                 .event_mask(EventMask::ButtonPress
                                           | EventMask::ButtonRelease
-                                          | EventMask::Exposure)
+                                          | EventMask::Exposure
+                                          | EventMask::EnterWindow
+                                          | EventMask::LeaveWindow
+                                          | EventMask::PointerMotion)
+        ).unwrap().check()?;
+
+        let trigger = conn.generate_id().unwrap();
+        conn.create_window(0, trigger, root,
+                           0,0,1,1, 0, WindowClass::InputOnly, x11rb::COPY_FROM_PARENT,
+                           &CreateWindowAux::new()
+                                .override_redirect(1)
+                                .event_mask(EventMask::ButtonPress
+                                          | EventMask::ButtonRelease
+                                          | EventMask::EnterWindow
+                                          | EventMask::LeaveWindow
+                                          | EventMask::PointerMotion)
         ).unwrap().check()?;
 
+        // Selecting XInput motion events replaces the core ones, which are still reported during a button grab
+        let xinput = conn.xinput_xi_query_version(2, 1).ok()
+            .and_then(|x| x.reply().ok())
+            .map(|x| (x.major_version, x.minor_version) >= (2, 1))
+            .unwrap_or(false);
+        if xinput {
+            let mask = [xinput::EventMask {
+                deviceid: xinput::Device::AllMaster.into(),
+                mask: vec!(xinput::XIEventMask::Motion as u32 | xinput::XIEventMask::DeviceChanged as u32)
+            }];
+            conn.xinput_xi_select_events(window, &mask)?.check()?;
+            conn.xinput_xi_select_events(trigger, &mask)?.check()?;
+        }
+
         
         conn.change_property8(PropMode::Replace, window, AtomEnum::WM_NAME, AtomEnum::STRING, b"Ravenbar").unwrap();
 
@@ -267,7 +310,8 @@ impl Window {
         let fc = Fontconfig::new().expect("Failed to initialize Fontconfig");
         let ft = Library::init().expect("Failed to initialize Freetype");
 
-        let wnd = Window {window, colormap, conn, surface, ctx, screen, depth, atoms, fc, ft, root};
+        let wnd = Window {window, colormap, conn, surface, ctx, screen, depth, atoms, fc, ft, root, trigger, xinput,
+                         pointer: Cell::new(None), press: Cell::new(None), scroll: Cell::new((0.0, 0.0))};
 
         Ok(wnd)
     }
@@ -303,6 +347,23 @@ impl Window {
         Ok(())
     }
 
+    /// Map the trigger window over the given area, or unmap it
+    pub fn configure_trigger(&self, geom: Option<&WindowGeometry>) -> Result<(), Box<dyn Error>> {
+        match geom {
+            Some(geom) => {
+                let (x,y,w,h) = geom.on_screen(self.screen.width_in_pixels, self.screen.height_in_pixels);
+                let aux = &ConfigureWindowAux::new().x(x as i32).y(y as i32).width(w.max(1) as u32).height(h.max(1) as u32)
+                    .stack_mode(StackMode::Above);
+
+                self.conn.configure_window(self.trigger, aux)?;
+                self.conn.map_window(self.trigger)?;
+            }
+            None => {self.conn.unmap_window(self.trigger)?;}
+        }
+        self.flush();
+        Ok(())
+    }
+
     pub fn set_atom32(&self, atom: u32, atype: AtomEnum, data: &[u32]) -> Result<(), Box<dyn Error>>{
         self.conn.change_property32(PropMode::Replace, self.window, atom, atype, data)?;
         Ok(())
@@ -316,15 +377,39 @@ impl Window {
         self.screen.height_in_pixels
     }
 
-    pub fn get_pointer(&self) -> (i16, i16) {
-        const E: &str = "Failed to query pointer";
-        
-        let pointer = self.conn.query_pointer(self.root).expect(E).reply().expect(E);
-        
-        (pointer.root_x, pointer.root_y)
+    /// Last pointer position reported by X events, `None` if the pointer isn't over the bar
+    pub fn get_pointer(&self) -> Option<(i16, i16)> {
+        self.pointer.get()
+    }
+
+    pub fn set_pointer(&self, pointer: Option<(i16, i16)>) {
+        self.pointer.set(pointer);
+    }
+
+    /// Button being held along with where it was pressed, `None` if no button is held
+    pub fn get_press(&self) -> Option<(u8, i16, i16)> {
+        self.press.get()
+    }
+
+    pub fn set_press(&self, press: Option<(u8, i16, i16)>) {
+        self.press.set(press);
+    }
+
+    /// Horizontal and vertical smooth scrolling since the last refresh, in scroll steps
+    pub fn get_scroll(&self) -> (f64, f64) {
+        self.scroll.get()
+    }
+
+    pub fn set_scroll(&self, scroll: (f64, f64)) {
+        self.scroll.set(scroll);
     }
 
     pub fn flush(&self) {
         self.conn.flush().expect("Failed to flush the connection")
     }
+
+    /// X connection's descriptor, readable when events arrive
+    pub fn fd(&self) -> RawFd {
+        self.conn.as_raw_fd()
+    }
 }